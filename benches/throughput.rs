@@ -0,0 +1,157 @@
+//! Benchmarks of the hot paths. The SIMD stem vectors and the scalar fallback are
+//! compared by running `cargo bench` and `cargo bench --no-default-features`.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use ranger::{char_to_stem_index, Design, ProductionLine, Size, Species, Warehouse};
+
+const SAMPLE: &str = include_str!("../samples/10k.txt");
+const STEMS: usize = 100_000;
+const SEED: u64 = 0x5eed;
+
+/// Xorshift64, seeded with `SEED` so every run uses the same stem stream.
+struct Rng(u64);
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// The design lines of the sample, leaving out the ones that don't parse.
+fn design_lines() -> Vec<&'static str> {
+    SAMPLE
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .filter(|line| line.parse::<Design>().is_ok())
+        .collect()
+}
+
+/// A production line with the large designs of the sample.
+fn production_line() -> ProductionLine {
+    let mut production_line = ProductionLine::default();
+    for line in design_lines() {
+        let design: Design = line.parse().unwrap();
+        if design.size() == Size::Large {
+            production_line.add_design(design).unwrap();
+        }
+    }
+    production_line.preprocess();
+    production_line
+}
+
+fn stems() -> Vec<Species> {
+    let mut rng = Rng(SEED);
+    (0..STEMS)
+        .map(|_| char_to_stem_index(char::from(b'a' + (rng.next() % 26) as u8)).unwrap())
+        .collect()
+}
+
+fn parse(c: &mut Criterion) {
+    let lines = design_lines();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(lines.len() as u64));
+    group.bench_function("designs", |b| {
+        b.iter(|| {
+            for line in &lines {
+                std::hint::black_box(line.parse::<Design>().unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn preprocess(c: &mut Criterion) {
+    let production_line = production_line();
+    c.bench_function("preprocess", |b| {
+        b.iter_batched(
+            || production_line.clone(),
+            |mut production_line| {
+                production_line.preprocess();
+                production_line
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn add_stem(c: &mut Criterion) {
+    let production_line = production_line();
+    let stems = stems();
+    let run = |mut production_line: ProductionLine| {
+        stems
+            .iter()
+            .filter(|species| production_line.add_stem(**species).unwrap().is_some())
+            .count()
+    };
+    // Reported as bouquets per second.
+    let bouquets = run(production_line.clone());
+    let mut group = c.benchmark_group("add_stem");
+    group.throughput(Throughput::Elements(bouquets as u64));
+    group.bench_function("stream", |b| {
+        b.iter_batched(|| production_line.clone(), run, BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+/// The whole input as text, so reading the lines is included.
+fn process(c: &mut Criterion) {
+    let mut input: String = design_lines()
+        .iter()
+        .map(|line| format!("{line}\n"))
+        .collect();
+    input.push('\n');
+    for species in stems() {
+        input.push_str(&format!("{species}L\n"));
+    }
+    let mut group = c.benchmark_group("process");
+    group.throughput(Throughput::Elements(STEMS as u64));
+    group.bench_function("lines", |b| {
+        b.iter(|| {
+            let mut warehouse = Warehouse::with_writer(std::io::sink());
+            warehouse.on_invalid_design(|_, _| {});
+            warehouse.process(input.as_bytes()).unwrap();
+        })
+    });
+    group.finish();
+}
+
+/// Designs that each need many stems of 'a', fed a glut of 'a' with the occasional
+/// 'b', so most stems can't help any design yet.
+fn add_stem_glut(c: &mut Criterion) {
+    let mut production_line = ProductionLine::default();
+    for (name, total) in ('A'..='Z').zip(20..) {
+        let design: Design = format!("{name}L{total}a1b{total}").parse().unwrap();
+        production_line.add_design(design).unwrap();
+    }
+    production_line.preprocess();
+    let mut rng = Rng(SEED);
+    let stems: Vec<Species> = (0..STEMS)
+        .map(|_| {
+            char_to_stem_index(if rng.next().is_multiple_of(20) {
+                'b'
+            } else {
+                'a'
+            })
+            .unwrap()
+        })
+        .collect();
+    let mut group = c.benchmark_group("add_stem");
+    group.throughput(Throughput::Elements(STEMS as u64));
+    group.bench_function("glut", |b| {
+        b.iter_batched(
+            || production_line.clone(),
+            |mut production_line| {
+                for species in &stems {
+                    std::hint::black_box(production_line.add_stem(*species).unwrap());
+                }
+                production_line
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, preprocess, add_stem, add_stem_glut, process);
+criterion_main!(benches);
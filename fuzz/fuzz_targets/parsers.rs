@@ -0,0 +1,39 @@
+#![no_main]
+//! Feeds arbitrary lines to the design and stem parsers, which must return an error
+//! instead of panicking. Designs that parse are checked and run on a production line.
+use libfuzzer_sys::fuzz_target;
+use ranger::{Design, ProductionLine, Species, Warehouse};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let (designs, stems) = input.split_once("\n\n").unwrap_or((input, ""));
+
+    let mut production_line = ProductionLine::default();
+    for line in designs.lines() {
+        let Ok(design) = line.parse::<Design>() else {
+            continue;
+        };
+        for species in Species::all() {
+            assert!(design.min_stems()[species] <= design.max_stems()[species], "{line}");
+        }
+        assert_eq!(design.to_string().parse::<Design>().as_ref(), Ok(&design));
+        production_line.add_design(design).unwrap();
+    }
+    production_line.preprocess();
+    for species in Species::all() {
+        let _ = production_line.add_stem(species);
+    }
+    production_line.drain();
+
+    let mut warehouse = Warehouse::with_writer(std::io::sink());
+    for line in designs.lines() {
+        let _ = warehouse.add_design(line);
+    }
+    warehouse.preprocess();
+    for line in stems.lines() {
+        let _ = warehouse.add_stem(line);
+    }
+    warehouse.drain();
+});
@@ -0,0 +1,242 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::design::Design;
+use crate::size::{Size, SizeMap};
+use crate::species::{Species, Stems};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum SpeciesOrder {
+    #[default]
+    Ascending,
+    /// Species with the largest amount in the bouquet first.
+    DescendingAmount,
+    /// Species with the largest maximum in the design of the bouquet first, e.g. to pack
+    /// the main species of a design first. `Bouquet::write_to` doesn't know the design,
+    /// so there the amounts in the bouquet stand in for the maximums.
+    DescendingMax,
+    /// Species in the given order, followed by any unlisted species in ascending order.
+    /// Values that are not species are left out, and a repeated species is only listed at its
+    /// first place.
+    Custom(Vec<char>),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Latency {
+    /// Flush the writer after every bouquet.
+    Low,
+    /// Leave flushing to the writer, and flush once at the end of the run.
+    #[default]
+    Throughput,
+}
+
+/// How a bouquet is written, each on its own line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The bouquet line, e.g. `AL5a`.
+    #[default]
+    Compact,
+    /// An object per line, e.g. `{"design":"A","size":"L","stems":{"a":5}}`.
+    Json,
+    /// The design, size and stems columns, e.g. `A,L,5a`.
+    Csv,
+}
+impl OutputFormat {
+    /// The line to write before the first bouquet, if any.
+    pub fn header(&self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Csv => Some("design,size,stems"),
+            OutputFormat::Compact | OutputFormat::Json => None,
+        }
+    }
+}
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(input: &str) -> Result<OutputFormat, Self::Err> {
+        match input {
+            "compact" => Ok(OutputFormat::Compact),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("Invalid format: {input}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutputConfig {
+    pub species_order: SpeciesOrder,
+    pub format: OutputFormat,
+    pub latency: Latency,
+    /// Write a `# heartbeat` line to the writer of each size when no bouquet was written
+    /// for this long, when processing with `Warehouse::process_streaming`.
+    pub heartbeat: Option<Duration>,
+}
+
+/// The writers of the bouquets, either one for all sizes or one per size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Writers<W> {
+    Shared(W),
+    PerSize(SizeMap<W>),
+}
+impl<W: Write> Writers<W> {
+    pub fn get_mut(&mut self, size: Size) -> &mut W {
+        match self {
+            Writers::Shared(writer) => writer,
+            Writers::PerSize(writers) => writers.get_mut(size),
+        }
+    }
+    /// Calls `f` once for every distinct writer.
+    pub fn try_for_each(&mut self, mut f: impl FnMut(&mut W) -> io::Result<()>) -> io::Result<()> {
+        match self {
+            Writers::Shared(writer) => f(writer),
+            Writers::PerSize(writers) => writers.values_mut().try_for_each(f),
+        }
+    }
+}
+
+/// A writer that writes everything to both of its writers, e.g. to stdout and a log
+/// file. A failed write or flush names the writer that failed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tee<A, B>(pub A, pub B);
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let first = self.0.write_all(buf);
+        let second = self.1.write_all(buf);
+        both(first, second)?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        let first = self.0.flush();
+        let second = self.1.flush();
+        both(first, second)
+    }
+}
+
+/// Combines the results of the two writers of a `Tee`.
+fn both(first: io::Result<()>, second: io::Result<()>) -> io::Result<()> {
+    match (first, second) {
+        (Ok(()), Ok(())) => Ok(()),
+        (Err(error), Ok(())) => Err(io::Error::new(
+            error.kind(),
+            format!("first writer: {error}"),
+        )),
+        (Ok(()), Err(error)) => Err(io::Error::new(
+            error.kind(),
+            format!("second writer: {error}"),
+        )),
+        (Err(first), Err(second)) => Err(io::Error::new(
+            first.kind(),
+            format!("first writer: {first}, second writer: {second}"),
+        )),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bouquet {
+    pub name: char,
+    pub size: Size,
+    pub stems: Stems,
+}
+impl Bouquet {
+    pub fn write_to(&self, out: &mut impl Write, config: &OutputConfig) -> io::Result<()> {
+        self.write_ordered(out, config, self.stems)
+    }
+    /// Writes the bouquet like `write_to`, with the maximums of `design` for
+    /// `SpeciesOrder::DescendingMax`.
+    pub fn write_for_design(
+        &self,
+        out: &mut impl Write,
+        config: &OutputConfig,
+        design: &Design,
+    ) -> io::Result<()> {
+        self.write_ordered(out, config, design.max_stems())
+    }
+    fn write_ordered(
+        &self,
+        out: &mut impl Write,
+        config: &OutputConfig,
+        max_stems: Stems,
+    ) -> io::Result<()> {
+        let stems = self
+            .species_in_order(&config.species_order, max_stems)
+            .into_iter()
+            .map(|species| (species, self.stems[species]))
+            .filter(|(_, amount)| *amount != 0);
+        match config.format {
+            OutputFormat::Compact => {
+                write!(out, "{}{}", self.name, self.size)?;
+                for (species, amount) in stems {
+                    write!(out, "{amount}{species}")?;
+                }
+            }
+            OutputFormat::Json => {
+                // Names, sizes and species never need escaping.
+                write!(
+                    out,
+                    r#"{{"design":"{}","size":"{}","stems":{{"#,
+                    self.name, self.size
+                )?;
+                for (position, (species, amount)) in stems.enumerate() {
+                    if position != 0 {
+                        write!(out, ",")?;
+                    }
+                    write!(out, r#""{species}":{amount}"#)?;
+                }
+                write!(out, "}}}}")?;
+            }
+            OutputFormat::Csv => {
+                write!(out, "{},{},", self.name, self.size)?;
+                for (species, amount) in stems {
+                    write!(out, "{amount}{species}")?;
+                }
+            }
+        }
+        writeln!(out)
+    }
+    fn species_in_order(&self, species_order: &SpeciesOrder, max_stems: Stems) -> Vec<Species> {
+        match species_order {
+            SpeciesOrder::Ascending => Species::all().collect(),
+            SpeciesOrder::DescendingAmount => {
+                let mut order: Vec<Species> = Species::all().collect();
+                order.sort_by_key(|species| std::cmp::Reverse(self.stems[*species]));
+                order
+            }
+            SpeciesOrder::DescendingMax => {
+                let mut order: Vec<Species> = Species::all().collect();
+                order.sort_by_key(|species| std::cmp::Reverse(max_stems[*species]));
+                order
+            }
+            SpeciesOrder::Custom(order) => {
+                let mut listed = Vec::new();
+                for species in order
+                    .iter()
+                    .filter_map(|species| Species::from_char(*species))
+                {
+                    if !listed.contains(&species) {
+                        listed.push(species);
+                    }
+                }
+                let unlisted: Vec<Species> = Species::all()
+                    .filter(|species| !listed.contains(species))
+                    .collect();
+                listed.extend(unlisted);
+                listed
+            }
+        }
+    }
+}
+/// The bouquet line with species in ascending order, e.g. `AL5a`.
+impl fmt::Display for Bouquet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.name, self.size)?;
+        for species in Species::all() {
+            let amount = self.stems[species];
+            if amount != 0 {
+                write!(f, "{amount}{species}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::{self, Write};
+
+use crate::bouquet::{OutputConfig, Writers};
+use crate::production_line::{Duplicates, Strategy, TieBreak};
+use crate::size::{Size, SizeMap};
+use crate::species::StemTotal;
+use crate::warehouse::{InputError, Warehouse};
+
+/// The settings of a warehouse that don't depend on the writer.
+#[derive(Clone, Debug, Default)]
+struct Settings {
+    output_config: OutputConfig,
+    alphabet: Option<String>,
+    default_size: Option<Size>,
+    separator: String,
+    strategy: Strategy,
+    tie_break: TieBreak,
+    duplicates: Duplicates,
+    capacity: SizeMap<Option<StemTotal>>,
+    cascade: bool,
+    parallel: bool,
+    strict: bool,
+}
+
+/// Configures a warehouse in one place, e.g.
+/// `WarehouseBuilder::default().writer(out).strategy(Strategy::MinimizeExcess).build()`.
+/// The setters do the same as the `set_` methods of `Warehouse`.
+#[derive(Debug)]
+pub struct WarehouseBuilder<W: Write = io::Stdout> {
+    writers: Writers<W>,
+    settings: Settings,
+}
+impl Default for WarehouseBuilder {
+    fn default() -> Self {
+        Self {
+            writers: Writers::Shared(io::stdout()),
+            settings: Settings::default(),
+        }
+    }
+}
+impl<W: Write> WarehouseBuilder<W> {
+    /// Writes the bouquets of all sizes to `writer`.
+    pub fn writer<V: Write>(self, writer: V) -> WarehouseBuilder<V> {
+        WarehouseBuilder {
+            writers: Writers::Shared(writer),
+            settings: self.settings,
+        }
+    }
+    /// Writes the bouquets of every size to the writer of that size.
+    pub fn writers<V: Write>(self, writers: SizeMap<V>) -> WarehouseBuilder<V> {
+        WarehouseBuilder {
+            writers: Writers::PerSize(writers),
+            settings: self.settings,
+        }
+    }
+    pub fn output_config(mut self, output_config: OutputConfig) -> Self {
+        self.settings.output_config = output_config;
+        self
+    }
+    /// The alphabet is checked by `build`.
+    pub fn alphabet(mut self, alphabet: &str) -> Self {
+        self.settings.alphabet = Some(alphabet.to_owned());
+        self
+    }
+    pub fn default_size(mut self, default_size: Size) -> Self {
+        self.settings.default_size = Some(default_size);
+        self
+    }
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.settings.separator = separator.to_owned();
+        self
+    }
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.settings.strategy = strategy;
+        self
+    }
+    pub fn tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.settings.tie_break = tie_break;
+        self
+    }
+    pub fn duplicates(mut self, duplicates: Duplicates) -> Self {
+        self.settings.duplicates = duplicates;
+        self
+    }
+    pub fn capacity(mut self, size: Size, capacity: StemTotal) -> Self {
+        self.settings.capacity[size] = Some(capacity);
+        self
+    }
+    pub fn cascade(mut self, cascade: bool) -> Self {
+        self.settings.cascade = cascade;
+        self
+    }
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.settings.parallel = parallel;
+        self
+    }
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.settings.strict = strict;
+        self
+    }
+    /// Makes the warehouse, or fails on an invalid alphabet.
+    pub fn build(self) -> Result<Warehouse<W>, InputError> {
+        let settings = self.settings;
+        let mut warehouse = Warehouse::with_output(self.writers);
+        if let Some(alphabet) = &settings.alphabet {
+            warehouse.set_alphabet(alphabet)?;
+        }
+        warehouse.set_output_config(settings.output_config);
+        warehouse.set_default_size(settings.default_size);
+        warehouse.set_separator(&settings.separator);
+        warehouse.set_strategy(settings.strategy);
+        warehouse.set_tie_break(settings.tie_break);
+        warehouse.set_duplicates(settings.duplicates);
+        for (size, capacity) in settings.capacity.iter() {
+            warehouse.set_capacity(size, *capacity);
+        }
+        warehouse.set_cascade(settings.cascade);
+        warehouse.set_parallel(settings.parallel);
+        warehouse.set_strict(settings.strict);
+        Ok(warehouse)
+    }
+}
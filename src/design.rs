@@ -0,0 +1,437 @@
+use std::fmt;
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::size::Size;
+use crate::species::{
+    stem_index_to_char, stem_total, Species, StemCount, StemTotal, Stems, SPECIES, SPECIES_CLASS,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Design {
+    pub(crate) name: char,
+    pub(crate) size: Size,
+    pub(crate) total: StemTotal,
+    pub(crate) min_stems: Stems,
+    pub(crate) max_stems: Stems,
+    /// Revenue of a bouquet of this design, set with a `$price` suffix on the design
+    /// line, e.g. `AL5a5$12`.
+    pub(crate) price: u32,
+    pub(crate) group: Option<String>,
+    /// The only species of a single-species design.
+    pub(crate) single_species: Option<Species>,
+    /// Only stems that arrived at most this many stems ago are used for this design.
+    pub(crate) max_age: Option<u64>,
+    /// At most this many bouquets are made of this design.
+    pub(crate) quota: Option<u32>,
+}
+impl Design {
+    pub fn name(&self) -> char {
+        self.name
+    }
+    pub fn size(&self) -> Size {
+        self.size
+    }
+    pub fn total(&self) -> StemTotal {
+        self.total
+    }
+    /// The minimum per species, after the optimization passes.
+    pub fn min_stems(&self) -> Stems {
+        self.min_stems
+    }
+    /// The maximum per species, after the optimization passes.
+    pub fn max_stems(&self) -> Stems {
+        self.max_stems
+    }
+    pub fn price(&self) -> u32 {
+        self.price
+    }
+    /// Creates a design from the maximum per species and the total, as in the design
+    /// line `AL10a5b12`.
+    pub fn new(
+        name: char,
+        size: Size,
+        stems: &[(char, StemCount)],
+        total: StemTotal,
+    ) -> Result<Design, DesignParseError> {
+        if !name.is_ascii_uppercase() {
+            // The name labels the bouquets, and is written as is, e.g. in JSON.
+            return Err(DesignParseError::InvalidName(name.to_string()));
+        }
+        let mut min_stems = Stems::splat(0);
+        let mut max_stems = Stems::splat(0);
+        if stems.is_empty() {
+            // A bouquet without stems could be made from any stock, over and over.
+            return Err(DesignParseError::NoSpecies);
+        }
+        let mut unique_stem_count = 0;
+        for (species, max) in stems {
+            let value = *species;
+            let species =
+                Species::from_char(value).ok_or(DesignParseError::UnknownSpecies(value))?;
+            if min_stems[species] != 0 {
+                return Err(DesignParseError::DuplicateSpecies(value));
+            }
+            min_stems[species] = 1;
+            max_stems[species] = *max;
+            unique_stem_count += 1;
+        }
+        if total < unique_stem_count {
+            // Every species takes at least one stem.
+            return Err(DesignParseError::TooFewStems);
+        }
+
+        // @Optimization - Minimize the maximum amount of stems.
+        //
+        // For example, given the design "AL10a5", it is obvious that the maximum
+        // possible amount for 'a' would be '5', so this routine updates it to "AL5a5".
+        //
+        // This reduces the posibility of grabbing too many stems from the stock, which
+        // costs precious time to put back.
+        let max_per_stem = 1 + total - unique_stem_count;
+        for stem_max in max_stems.as_mut_array().iter_mut() {
+            if StemTotal::from(*stem_max) > max_per_stem {
+                *stem_max = max_per_stem as StemCount;
+            }
+        }
+
+        // @Optimize - Specify minimum possible amounts per stem.
+        //
+        // For example, given the design "AL5a5", the only possible bouquet that can be
+        // created is "AL5a5".
+        //
+        // The minimum can be computed with the following formula:
+        //
+        //     stem_min = max(1, stem_max - sum(other_stems_max))
+        //
+        // Specifying a minimum amount required per species could allow for stopping
+        // the design check early, or perhaps even disregard multiple designs altogether
+        // with a SIMD operation.
+        {
+            let sum_max = stem_total(max_stems);
+            for (stem_index, stem_max) in max_stems.as_array().iter().enumerate() {
+                if *stem_max == 0 {
+                    continue;
+                }
+                let stem_max = u32::from(*stem_max);
+                let other_stems_max = sum_max.saturating_sub(stem_max);
+                min_stems[stem_index] =
+                    stem_max.saturating_sub(other_stems_max).max(1) as StemCount;
+            }
+        }
+
+        if let Some(species) =
+            Species::all().find(|species| min_stems[*species] > max_stems[*species])
+        {
+            return Err(DesignParseError::InconsistentConstraints(species.to_char()));
+        }
+
+        let species_mask = max_stems.nonzero();
+        let single_species = (species_mask.count_ones() == 1)
+            .then(|| Species::from_index(species_mask.trailing_zeros() as usize));
+
+        Ok(Design {
+            name,
+            size,
+            total,
+            min_stems,
+            max_stems,
+            price: 0,
+            group: None,
+            single_species,
+            max_age: None,
+            quota: None,
+        })
+    }
+    /// Takes exactly `total` stems for a bouquet from `stems`, also returning the excess
+    /// stems that were grabbed but put back. The excess is returned from the species of
+    /// `trim_order` first, in that order.
+    pub(crate) fn take(&self, stems: Stems, trim_order: &[Species]) -> Option<(Stems, Stems)> {
+        if let Some(species) = self.single_species {
+            // @Optimization - A single-species design only needs a scalar comparison,
+            // and never grabs excess stems.
+            let available = stems[species].min(self.max_stems[species]);
+            if StemTotal::from(available) < self.total {
+                return None;
+            }
+            let mut taken_stems = Stems::splat(0);
+            taken_stems[species] = self.total as StemCount;
+            return Some((taken_stems, Stems::splat(0)));
+        }
+        let grabbed_stems = self.grab(stems)?;
+        let mut taken_stems = grabbed_stems;
+        // Only stems above the minimum of a species can be returned, which always covers
+        // the excess: `new` never raises the minimums above the total together.
+        let mut excess_amount = stem_total(grabbed_stems) - self.total;
+        if excess_amount != 0 {
+            for species in trim_order {
+                let return_amount = StemTotal::min(
+                    StemTotal::from(taken_stems[*species] - self.min_stems[*species]),
+                    excess_amount,
+                );
+                taken_stems[*species] -= return_amount as StemCount;
+                excess_amount -= return_amount;
+            }
+        }
+        if excess_amount != 0 {
+            // The rest of the excess is returned in proportion to the surplus of every
+            // species, so no species is drained first, and what's left after rounding
+            // down one stem per species.
+            let excess_stems = taken_stems - self.min_stems;
+            let surplus = stem_total(excess_stems);
+            let mut remainder = excess_amount;
+            for stem_index in 0..SPECIES {
+                // The product of two totals may not fit a total.
+                let return_amount = (u64::from(excess_amount) * u64::from(excess_stems[stem_index])
+                    / u64::from(surplus)) as StemTotal;
+                remainder -= return_amount;
+                taken_stems[stem_index] -= return_amount as StemCount;
+            }
+            for stem_index in 0..SPECIES {
+                if remainder == 0 {
+                    break;
+                }
+                if taken_stems[stem_index] > self.min_stems[stem_index] {
+                    taken_stems[stem_index] -= 1;
+                    remainder -= 1;
+                }
+            }
+        }
+        Some((taken_stems, grabbed_stems - taken_stems))
+    }
+    /// The stems of a bouquet of this design: the minimums, topped up to the total
+    /// with the first species up to their maximums.
+    pub(crate) fn sample_bouquet(&self) -> Stems {
+        let mut stems = self.min_stems;
+        let mut missing = self.total - stem_total(self.min_stems);
+        for species in Species::all() {
+            if missing == 0 {
+                break;
+            }
+            let extra = u32::min(
+                u32::from(self.max_stems[species] - self.min_stems[species]),
+                missing,
+            );
+            stems[species] += extra as StemCount;
+            missing -= extra;
+        }
+        stems
+    }
+    /// Takes the maximum amount of stems this design can use from `stems`, or `None`
+    /// if the stems are not sufficient. The result may exceed `total`.
+    fn grab(&self, stems: Stems) -> Option<Stems> {
+        let grabbed_stems = stems.min(self.max_stems);
+        if stem_total(grabbed_stems) < self.total {
+            return None;
+        }
+        if grabbed_stems.lanes_lt(self.min_stems) != 0 {
+            return None;
+        }
+        Some(grabbed_stems)
+    }
+    pub fn can_make(&self, stems: Stems) -> bool {
+        self.grab(stems).is_some()
+    }
+    /// Upper bound of the amount of bouquets that could be made from `stems`.
+    pub fn max_bouquets(&self, stems: Stems) -> StemTotal {
+        let by_species = Stems::select(
+            self.min_stems.nonzero(),
+            stems / self.min_stems.max(Stems::splat(1)),
+            Stems::splat(StemCount::MAX),
+        )
+        .reduce_min();
+        let usable: u32 = stems
+            .as_array()
+            .iter()
+            .zip(self.max_stems.as_array())
+            .map(|(stock, max)| {
+                u32::min(u32::from(*stock), u32::from(*max) * u32::from(by_species))
+            })
+            .sum();
+        let by_total = usable / self.total;
+        StemTotal::min(StemTotal::from(by_species), by_total)
+    }
+}
+/// The design line after the optimization passes, which parses back to the same design,
+/// e.g. `AL5a5` for `AL10a5`.
+impl fmt::Display for Design {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.name, self.size)?;
+        for species in Species::all() {
+            let max = self.max_stems[species];
+            if max != 0 {
+                write!(f, "{max}{}", stem_index_to_char(species))?;
+            }
+        }
+        write!(f, "{}", self.total)?;
+        if self.price != 0 {
+            write!(f, "${}", self.price)?;
+        }
+        Ok(())
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
+pub enum DesignParseError {
+    /// The line is not a name, a size and a list of stems.
+    NoMatch,
+    /// The line contains whitespace, e.g. `AL 10a 5`.
+    Whitespace,
+    /// The name is not an uppercase letter, e.g. `a` in `aL10a5` or `AB` in `ABL10a5`.
+    InvalidName(String),
+    InvalidSize(String),
+    MissingTotal,
+    /// A maximum, percentage or total that doesn't fit in a stem count.
+    BadStemCount,
+    InvalidPrice,
+    /// The minimum of a species exceeds its maximum after the optimization passes.
+    InconsistentConstraints(char),
+    /// The species appears more than once in the design, e.g. `AL3a4a5`.
+    DuplicateSpecies(char),
+    /// The design has no species, e.g. `AL0`.
+    NoSpecies,
+    /// A value of `Design::new` that is not a species, e.g. `?`.
+    UnknownSpecies(char),
+    /// The total is less than the number of species, e.g. `AL5a5b1`.
+    TooFewStems,
+}
+
+/// The name of a design line, if it isn't a single uppercase letter: the first
+/// character if that isn't an uppercase letter, e.g. `a` in `aL10a5`, or else the
+/// uppercase letters before the size, e.g. `AB` in `ABL10a5`, or before whitespace, e.g.
+/// `AB` in `AB L10a5`.
+fn invalid_name(input: &str) -> Option<&str> {
+    let first = input.chars().next()?;
+    if !first.is_ascii_uppercase() {
+        return Some(&input[..first.len_utf8()]);
+    }
+    let letters = input
+        .find(|value: char| !value.is_ascii_uppercase())
+        .map_or(input, |end| &input[..end]);
+    // The longest size the letters end in, e.g. `XL` rather than `L`.
+    let name = (1..letters.len())
+        .find(|split| Size::from_str(&letters[*split..]).is_ok())
+        .map(|split| &letters[..split]);
+    let followed_by_whitespace = input[letters.len()..].starts_with(char::is_whitespace);
+    match name {
+        Some(name) => (name.len() > 1).then_some(name),
+        None if followed_by_whitespace && letters.len() > 1 => Some(letters),
+        None => None,
+    }
+}
+
+impl FromStr for Design {
+    type Err = DesignParseError;
+    fn from_str(input: &str) -> Result<Design, Self::Err> {
+        lazy_static! {
+            static ref DESIGN_RE: Regex = Regex::new(
+                r"(?x)^
+                (?P<name>[A-Z])
+                (?P<size>[A-Z]+)
+                (?P<stems>.*?)
+                (?P<total>\d+)?
+                (?:\$(?P<price>\d+))?$"
+            )
+            .unwrap();
+            static ref STEMS_RE: Regex = Regex::new(&format!(
+                r"(?:(?P<max>\d+)(?P<percent>%)?)?(?P<species>{SPECIES_CLASS})"
+            ))
+            .unwrap();
+        }
+        // The name comes first, so a line with a bad name and whitespace reports the name.
+        if let Some(name) = invalid_name(input) {
+            return Err(DesignParseError::InvalidName(name.to_owned()));
+        }
+        if input.contains(char::is_whitespace) {
+            return Err(DesignParseError::Whitespace);
+        }
+        let design_match = DESIGN_RE.captures(input).ok_or(DesignParseError::NoMatch)?;
+        // The name is a single ASCII letter.
+        let name = char::from(design_match["name"].as_bytes()[0]);
+        let size = Size::from_str(&design_match["size"])
+            .map_err(|_| DesignParseError::InvalidSize(design_match["size"].to_owned()))?;
+        let total = design_match
+            .name("total")
+            .ok_or(DesignParseError::MissingTotal)?
+            .as_str()
+            .parse::<StemTotal>()
+            .map_err(|_| DesignParseError::BadStemCount)?;
+        let stems = STEMS_RE
+            .captures_iter(&design_match["stems"])
+            .map(|stem_match| {
+                let species = stem_match["species"]
+                    .chars()
+                    .next()
+                    .ok_or(DesignParseError::NoMatch)?;
+                let Some(max) = stem_match.name("max").map(|max| max.as_str()) else {
+                    // A species without a maximum, like `a` in `ALab3`, may fill the whole
+                    // bouquet, before leaving room for the other species.
+                    return Ok((
+                        species,
+                        StemCount::try_from(total).unwrap_or(StemCount::MAX),
+                    ));
+                };
+                let max = if stem_match.name("percent").is_some() {
+                    // A maximum like `60%a` is a share of the total, rounded down.
+                    let percentage = max
+                        .parse::<u32>()
+                        .map_err(|_| DesignParseError::BadStemCount)?;
+                    let max = u64::from(total) * u64::from(percentage) / 100;
+                    StemCount::try_from(max).unwrap_or(StemCount::MAX)
+                } else {
+                    max.parse::<StemCount>()
+                        .map_err(|_| DesignParseError::BadStemCount)?
+                };
+                Ok((species, max))
+            })
+            .collect::<Result<Vec<(char, StemCount)>, DesignParseError>>()?;
+        let mut design = Design::new(name, size, &stems, total)?;
+        if let Some(price) = design_match.name("price") {
+            design.price = price
+                .as_str()
+                .parse::<u32>()
+                .map_err(|_| DesignParseError::InvalidPrice)?;
+        }
+        Ok(design)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The scalar path of single-species designs takes the same stems as the general
+    /// path would, from any stock.
+    #[test]
+    fn single_species_path_takes_like_the_general_path() {
+        for line in ["AL5a5", "AL9b3", "AS1z1"] {
+            let design: Design = line.parse().unwrap();
+            assert!(design.single_species.is_some(), "{line}");
+            let general = Design {
+                single_species: None,
+                ..design.clone()
+            };
+            for amount in 0..12 {
+                let stems = Stems::splat(amount);
+                assert_eq!(design.take(stems, &[]), general.take(stems, &[]), "{line}");
+            }
+        }
+    }
+
+    #[test]
+    fn excess_is_not_trimmed_below_the_minimums() {
+        use crate::species::vec_to_stems;
+
+        let mut design = Design::from_str("AS3a3b3").unwrap();
+        design.min_stems = vec_to_stems(&[('a', 2), ('b', 1)]).unwrap();
+        assert_eq!(
+            design.take(vec_to_stems(&[('a', 3), ('b', 3)]).unwrap(), &[]),
+            Some((
+                vec_to_stems(&[('a', 2), ('b', 1)]).unwrap(),
+                vec_to_stems(&[('b', 1)]).unwrap()
+            ))
+        );
+    }
+}
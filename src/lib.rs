@@ -0,0 +1,38 @@
+#![cfg_attr(
+    all(
+        feature = "simd",
+        not(all(feature = "wide-alphabet", feature = "uppercase-species"))
+    ),
+    feature(portable_simd)
+)]
+//! Makes bouquets from a stream of stems, following a catalog of bouquet designs.
+mod bouquet;
+mod builder;
+mod design;
+mod observer;
+mod production_line;
+mod size;
+mod species;
+mod stem_vec;
+mod warehouse;
+
+pub use bouquet::{Bouquet, Latency, OutputConfig, OutputFormat, SpeciesOrder, Tee, Writers};
+pub use builder::WarehouseBuilder;
+pub use design::{Design, DesignParseError};
+#[cfg(feature = "log")]
+pub use observer::LogObserver;
+pub use observer::{EngineObserver, NoopObserver};
+pub use production_line::{
+    Coverage, DesignId, DesignIndex, Duplicates, ProductionLine, RegisterError, Stats, StemError,
+    StemParseError, StepResult, Strategy, TieBreak,
+};
+pub use size::{Size, SizeMap, SIZES};
+pub use species::{
+    char_to_stem_index, is_species, stem_index_to_char, stem_line, stem_total, stems_to_vec,
+    vec_to_stems, Species, StemCount, StemTotal, Stems, LANES, SPECIES,
+};
+pub use stem_vec::LaneMask;
+pub use warehouse::{
+    compare_catalogs, run, AddOutcome, DesignError, InputError, LineJoiner, Stock, Warehouse,
+    WarehouseState, DRAIN,
+};
@@ -1,292 +1,234 @@
-#![feature(portable_simd)]
-use std::default::Default;
-use std::simd::{u16x32, SimdOrd, SimdPartialOrd, SimdUint};
-use std::str::FromStr;
-use std::{io, io::Write};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::rc::Rc;
+use std::time::Instant;
 
-use lazy_static::lazy_static;
-use regex::Regex;
+use clap::{Parser, ValueEnum};
+use ranger::{InputError, OutputConfig, OutputFormat, Size, Warehouse};
 
-mod size;
-
-use size::{Size, SizeMap};
-
-fn char_to_stem_index(value: char) -> usize {
-    const LOWER_BOUND: usize = 'a' as usize;
-    value as usize - LOWER_BOUND
+/// Makes bouquets from the designs and stems on stdin, unless they are read from files.
+#[derive(Parser)]
+struct Args {
+    /// Reads the designs from a file, in which blank lines are ignored.
+    #[arg(long)]
+    designs: Option<PathBuf>,
+    /// Reads the stems from a file, in which blank lines are ignored.
+    #[arg(long)]
+    stems: Option<PathBuf>,
+    /// The line between the designs and the stems on stdin, and after the stems.
+    #[arg(long, default_value = "")]
+    separator: String,
+    /// Prints the stems left in stock to stderr, as stem lines, or with `--leftovers
+    /// json` as a JSON object of the stems per size, the most stems first.
+    #[arg(long, num_args = 0..=1, default_missing_value = "lines")]
+    leftovers: Option<Leftovers>,
+    /// Prints the bouquets made and stems consumed per design to stderr, followed by
+    /// the stems received, the bouquets made and the time taken by the stems.
+    #[arg(long)]
+    stats: bool,
+    /// Checks every design and exits without reading the stems, reporting the invalid
+    /// designs to stderr.
+    #[arg(long)]
+    check: bool,
+    /// Skips invalid designs and stems with a warning on stderr, instead of exiting at
+    /// the first one.
+    #[arg(long)]
+    skip_invalid: bool,
+    /// Writes the bouquets as `compact` lines like `AL5a`, or as `json` or `csv`.
+    #[arg(long, default_value = "compact")]
+    format: OutputFormat,
 }
-fn stem_index_to_char(stem_index: usize) -> char {
-    const LOWER_BOUND: usize = 'a' as usize;
-    char::from_u32((LOWER_BOUND + stem_index) as u32).unwrap()
+
+/// How `--leftovers` prints the stems left in stock.
+#[derive(Clone, Copy, ValueEnum)]
+enum Leftovers {
+    Lines,
+    Json,
 }
 
+/// Why a run failed, which decides the exit code.
 #[derive(Debug)]
-struct Design {
-    name: char,
-    size: Size,
-    total: u16,
-    min_stems: u16x32,
-    max_stems: u16x32,
+enum RangerError {
+    /// The designs or stems are malformed.
+    Input(InputError),
+    /// A file, stdin or stdout failed.
+    Io(io::Error),
 }
-impl FromStr for Design {
-    type Err = ();
-    fn from_str(input: &str) -> Result<Design, Self::Err> {
-        lazy_static! {
-            static ref DESIGN_RE: Regex = Regex::new(
-                r"(?x)^
-                (?P<name>[A-Z]
-                (?P<size>[SL]))
-                (?P<stems>.*?)
-                (?P<total>\d+)$"
-            )
-            .unwrap();
-            static ref STEMS_RE: Regex = Regex::new(r"(?P<max>\d+)(?P<species>[a-z])").unwrap();
-        }
-        let design_match = DESIGN_RE.captures(input).unwrap();
-        let name = design_match
-            .name("name")
-            .unwrap()
-            .as_str()
-            .chars()
-            .next()
-            .unwrap();
-        let size = Size::from_str(design_match.name("size").unwrap().as_str()).unwrap();
-        let total = design_match
-            .name("total")
-            .unwrap()
-            .as_str()
-            .parse::<u16>()
-            .unwrap();
-        let mut min_stems = u16x32::splat(0);
-        let mut max_stems = u16x32::splat(0);
-        let stems = design_match.name("stems").unwrap().as_str();
-        let mut unique_stem_count = 0;
-        for stem_match in STEMS_RE.captures_iter(stems) {
-            let stem_index = char_to_stem_index(
-                stem_match
-                    .name("species")
-                    .unwrap()
-                    .as_str()
-                    .chars()
-                    .next()
-                    .unwrap(),
-            );
-            let max = stem_match
-                .name("max")
-                .unwrap()
-                .as_str()
-                .parse::<u16>()
-                .unwrap();
-            min_stems[stem_index] = 1;
-            max_stems[stem_index] = max;
-            unique_stem_count += 1;
-        }
+/// The exit codes of `sysexits.h` for malformed input and failed I/O.
+const DATA_ERROR: u8 = 65;
+const IO_ERROR: u8 = 74;
 
-        // @Optimization - Minimize the maximum amount of stems.
-        //
-        // For example, given the design "AL10a5", it is obvious that the maximum
-        // possible amount for 'a' would be '5', so this routine updates it to "AL5a5".
-        //
-        // This reduces the posibility of grabbing too many stems from the stock, which
-        // costs precious time to put back.
-        {
-            let max_per_stem = 1 + total - unique_stem_count;
-            for stem_max in max_stems.as_mut_array().iter_mut() {
-                *stem_max = u16::min(*stem_max, max_per_stem);
-            }
+impl RangerError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            RangerError::Input(_) => ExitCode::from(DATA_ERROR),
+            RangerError::Io(_) => ExitCode::from(IO_ERROR),
         }
-
-        // @Optimize - Specify minimum possible amounts per stem.
-        //
-        // For example, given the design "AL5a5", the only possible bouquet that can be
-        // created is "AL5a5".
-        //
-        // The minimum can be computed with the following formula:
-        //
-        //     stem_min = max(1, stem_max - sum(other_stems_max))
-        //
-        // Specifying a minimum amount required per species could allow for stopping
-        // the design check early, or perhaps even disregard multiple designs altogether
-        // with a SIMD operation.
-        {
-            let sum_max: u16 = max_stems.reduce_sum();
-            for (stem_index, stem_max) in max_stems.as_array().iter().enumerate() {
-                if *stem_max == 0 {
-                    continue;
-                }
-                min_stems[stem_index] =
-                    u16::max(1, stem_max - u16::min(*stem_max, sum_max - stem_max));
-            }
+    }
+}
+impl From<InputError> for RangerError {
+    fn from(error: InputError) -> Self {
+        match error {
+            InputError::Io(error) => RangerError::Io(error),
+            error => RangerError::Input(error),
         }
-
-        Ok(Design {
-            name,
-            size,
-            total,
-            min_stems,
-            max_stems,
-        })
+    }
+}
+impl From<io::Error> for RangerError {
+    fn from(error: io::Error) -> Self {
+        RangerError::Io(error)
     }
 }
 
-#[derive(Debug)]
-struct ProductionLine {
-    stems: u16x32,
-    designs: [Option<Design>; 26],
-    add_design_index: usize,
-    designs_per_stem: [[usize; 26]; 26],
-    max_per_stem: [u16; 26],
+fn open(path: &Path) -> Result<BufReader<File>, RangerError> {
+    match File::open(path) {
+        Ok(file) => Ok(BufReader::new(file)),
+        Err(error) => Err(RangerError::Io(io::Error::new(
+            error.kind(),
+            format!("can't open {}: {error}", path.display()),
+        ))),
+    }
 }
-impl Default for ProductionLine {
-    fn default() -> Self {
-        Self {
-            stems: u16x32::splat(0),
-            designs: [
-                None, None, None, None, None, None, None, None, None, None, None, None, None, None,
-                None, None, None, None, None, None, None, None, None, None, None, None,
-            ],
-            add_design_index: 0,
-            designs_per_stem: [[usize::MAX; 26]; 26],
-            max_per_stem: [0; 26],
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            match &error {
+                RangerError::Input(error) => eprintln!("Invalid input: {error:?}"),
+                RangerError::Io(error) => eprintln!("I/O error: {error}"),
+            }
+            error.exit_code()
         }
     }
 }
-impl ProductionLine {
-    pub fn add_design(&mut self, design: Design) {
-        for (stem_index, amount) in design.max_stems.as_array().iter().enumerate() {
-            if *amount != 0 {
-                self.max_per_stem[stem_index] = u16::max(self.max_per_stem[stem_index], *amount);
-                for (insert_index, design_index) in
-                    self.designs_per_stem[stem_index].iter().enumerate()
-                {
-                    if *design_index == usize::MAX {
-                        self.designs_per_stem[stem_index][insert_index] = self.add_design_index;
-                        break;
-                    }
-                }
+
+fn run(args: Args) -> Result<ExitCode, RangerError> {
+    // The bouquets are flushed at the end of the run. A failed write is kept until
+    // then, as the handler can't return it.
+    let out = Rc::new(RefCell::new(BufWriter::new(io::stdout().lock())));
+    let write_error: Rc<RefCell<Option<io::Error>>> = Rc::default();
+    let output_config = OutputConfig {
+        format: args.format,
+        ..OutputConfig::default()
+    };
+    if let Some(header) = output_config.format.header() {
+        writeln!(out.borrow_mut(), "{header}")?;
+    }
+    let mut warehouse = {
+        let (out, write_error) = (Rc::clone(&out), Rc::clone(&write_error));
+        Warehouse::with_handler(move |bouquet| {
+            if let Err(error) = bouquet.write_to(&mut *out.borrow_mut(), &output_config) {
+                write_error.borrow_mut().get_or_insert(error);
             }
-        }
-        self.designs[self.add_design_index] = Some(design);
-        self.add_design_index += 1;
+        })
+    };
+    warehouse.set_separator(&args.separator);
+    warehouse.on_skipped_design(|line| {
+        eprintln!("Skipping design {line:?}: its species can't add up to its total")
+    });
+    if args.skip_invalid {
+        warehouse.on_invalid_design(|line, error| eprintln!("Skipping design {line:?}: {error:?}"));
+        warehouse.on_invalid_stem(|line, error| eprintln!("Skipping stem {line:?}: {error:?}"));
     }
-    pub fn preprocess(&mut self) {
-        // @Optimization - Sort the designs per stem array by total amount of stems to
-        // prioritize simpler designs.
-        for designs in self.designs_per_stem.iter_mut() {
-            designs.sort_by_key(|design_index| {
-                if *design_index == usize::MAX {
-                    return u16::MAX;
-                }
-                match &self.designs[*design_index] {
-                    Some(design) => design.total,
-                    None => u16::MAX,
-                }
-            });
+    // Warned once per species and size, as the stems of a species usually come in bulk.
+    let mut unused = HashSet::new();
+    warehouse.on_unused_stem(move |species, size| {
+        if unused.insert((species, size)) {
+            eprintln!("No design of size {size} uses stem {species}, it stays in stock");
         }
-    }
-    pub fn add_stem(&mut self, stem_index: usize) {
-        self.stems[stem_index] += 1;
-        if self.stems[stem_index] > self.max_per_stem[stem_index] {
-            // @Optimization - If we already surpassed the max required numbers of stems
-            // of this species for all designs, we can't make a new design this round.
-            return;
+    });
+    let mut stdin = io::stdin().lock();
+    if args.check {
+        let errors = match &args.designs {
+            Some(path) => warehouse.validate_designs(open(path)?)?,
+            None => warehouse.validate_designs(&mut stdin)?,
+        };
+        for (line, error) in &errors {
+            eprintln!("Invalid design {line:?}: {error:?}");
         }
-        for design_index in &self.designs_per_stem[stem_index] {
-            if *design_index == usize::MAX {
-                break;
-            }
-            let design_option = &self.designs[*design_index];
-            let design = match design_option {
-                Some(design) => design,
-                None => break,
-            };
-            let mut grabbed_stems = self.stems.simd_min(design.max_stems);
-            let grabbed_amount = grabbed_stems.reduce_sum();
-            if grabbed_amount < design.total {
-                continue;
-            }
-            if grabbed_stems.simd_lt(design.min_stems).any() {
-                continue;
-            }
-            let mut excess_amount = grabbed_amount - design.total;
-            if excess_amount != 0 {
-                let excess_stems = grabbed_stems - design.min_stems;
-                for stem_index in 0..26 {
-                    let stem_amount = excess_stems[stem_index];
-                    if stem_amount == 0 {
-                        continue;
-                    }
-                    let return_amount = u16::min(excess_amount, stem_amount);
-                    excess_amount -= return_amount;
-                    grabbed_stems[stem_index] -= return_amount;
-                    if excess_amount == 0 {
-                        break;
-                    }
-                }
-            }
-            self.stems -= grabbed_stems;
-            let out = io::stdout();
-            let mut handle = out.lock();
-            write!(handle, "{}{}", design.name, design.size).unwrap();
-            for stem_index in 0..26 {
-                let amount = grabbed_stems[stem_index];
-                if amount != 0 {
-                    write!(handle, "{}{}", amount, stem_index_to_char(stem_index)).unwrap();
+        return Ok(if errors.is_empty() {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::from(DATA_ERROR)
+        });
+    }
+    match &args.designs {
+        Some(path) => {
+            for line in open(path)?.lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    warehouse.process_line(&line)?;
                 }
             }
-            writeln!(handle).unwrap();
-            drop(handle);
-            return;
+            warehouse.end_designs()?;
         }
+        None => warehouse.process_designs(&mut stdin)?,
     }
-}
-
-#[derive(Debug, Default)]
-struct Warehouse {
-    production_lines: SizeMap<ProductionLine>,
-}
-
-impl Warehouse {
-    pub fn new() -> Self {
-        Self::default()
+    let start = Instant::now();
+    match &args.stems {
+        Some(path) => warehouse.process_stems(open(path)?)?,
+        None => warehouse.process(stdin)?,
     }
-    pub fn add_design(&mut self, design_str: &str) {
-        let design = Design::from_str(design_str).unwrap();
-        if design.total >= design.min_stems.reduce_sum() {
-            // Only push possible designs
-            self.production_lines
-                .get_mut(&design.size)
-                .add_design(design);
+    let elapsed = start.elapsed();
+    match args.leftovers {
+        Some(Leftovers::Lines) => {
+            // The leftovers are printed as stem lines, so they can be fed back in.
+            let remaining = warehouse.remaining_stems();
+            for (size, remaining) in remaining.iter() {
+                for (species, amount) in remaining {
+                    eprintln!("{amount}{species}{size}");
+                }
+            }
         }
-    }
-    pub fn preprocess(&mut self) {
-        self.production_lines.get_mut(&Size::Small).preprocess();
-        self.production_lines.get_mut(&Size::Large).preprocess();
-    }
-    pub fn add_stem(&mut self, stem_str: &str) {
-        let stem_index = char_to_stem_index(stem_str.chars().next().unwrap());
-        let size = Size::from_str(&stem_str[1..2]).unwrap();
-        self.production_lines.get_mut(&size).add_stem(stem_index);
-    }
-}
-
-fn main() {
-    let mut stdin = io::stdin().lines();
-    let lines = stdin.by_ref();
-    let mut warehouse = Warehouse::new();
-    for line in &mut *lines {
-        let line_ref = line.as_ref().unwrap();
-        if line_ref.is_empty() {
-            break;
+        Some(Leftovers::Json) => {
+            let sizes: Vec<String> = warehouse
+                .leftovers_report()
+                .iter()
+                .map(|(size, leftovers)| {
+                    let stems: Vec<String> = leftovers
+                        .iter()
+                        .map(|(species, amount)| {
+                            format!("{{\"species\":\"{species}\",\"amount\":{amount}}}")
+                        })
+                        .collect();
+                    format!("\"{size}\":[{}]", stems.join(","))
+                })
+                .collect();
+            eprintln!("{{{}}}", sizes.join(","));
         }
-        warehouse.add_design(line_ref.as_str());
+        None => {}
     }
-    warehouse.preprocess();
-    for line in &mut *lines {
-        let line_ref = line.as_ref().unwrap();
-        if line_ref.is_empty() {
-            break;
+    if args.stats {
+        let (bouquets, stems) = (warehouse.stats(), warehouse.consumed_stems());
+        for size in Size::ALL {
+            for ((name, bouquets), (_, stems)) in bouquets.get(size).iter().zip(stems.get(size)) {
+                eprintln!("{name}{size}: {bouquets} bouquets, {stems} stems");
+            }
         }
-        warehouse.add_stem(line_ref.as_str());
+        let stems = warehouse.stems_received();
+        let bouquets: u32 = bouquets
+            .values()
+            .flatten()
+            .map(|(_, bouquets)| bouquets)
+            .sum();
+        // A run without stems, or too short to measure, has a throughput of 0.
+        let per_second = match elapsed.as_secs_f64() {
+            seconds if stems != 0 && seconds > 0.0 => stems as f64 / seconds,
+            _ => 0.0,
+        };
+        eprintln!(
+            "{stems} stems, {bouquets} bouquets in {:.3}s, {per_second:.0} stems/s",
+            elapsed.as_secs_f64(),
+        );
+    }
+    if let Some(error) = write_error.take() {
+        return Err(RangerError::Io(error));
     }
+    out.borrow_mut().flush()?;
+    Ok(ExitCode::SUCCESS)
 }
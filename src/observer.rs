@@ -0,0 +1,58 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::bouquet::Bouquet;
+use crate::size::Size;
+
+/// Receives the events of a warehouse, for any observability backend. All methods do
+/// nothing by default.
+pub trait EngineObserver {
+    /// A stem was accepted.
+    fn on_stem(&mut self, _species: char, _size: Size) {}
+    /// A bouquet was made.
+    fn on_bouquet(&mut self, _bouquet: &Bouquet) {}
+    /// A design or stem line was not accepted.
+    fn on_reject(&mut self, _line: &str) {}
+    /// A design line was merged into the same design registered before, with
+    /// `Duplicates::Merge`.
+    fn on_merge(&mut self, _line: &str) {}
+    /// The last stem of a species in stock was used for a bouquet, which is reported
+    /// before the bouquet itself.
+    fn on_deplete(&mut self, _species: char, _size: Size) {}
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopObserver;
+impl EngineObserver for NoopObserver {}
+
+/// Writes the events to the `log` facade.
+#[cfg(feature = "log")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogObserver;
+#[cfg(feature = "log")]
+impl EngineObserver for LogObserver {
+    fn on_stem(&mut self, species: char, size: Size) {
+        log::trace!("stem {species}{size}");
+    }
+    fn on_bouquet(&mut self, bouquet: &Bouquet) {
+        log::debug!("bouquet {}{}", bouquet.name, bouquet.size);
+    }
+    fn on_reject(&mut self, line: &str) {
+        log::warn!("rejected {line:?}");
+    }
+    fn on_merge(&mut self, line: &str) {
+        log::debug!("merged {line:?}");
+    }
+    fn on_deplete(&mut self, species: char, size: Size) {
+        log::debug!("depleted {species}{size}");
+    }
+}
+
+/// An observer shared by a warehouse and the depletion hooks of its production lines.
+#[derive(Clone)]
+pub(crate) struct SharedObserver(pub(crate) Rc<RefCell<dyn EngineObserver>>);
+impl std::fmt::Debug for SharedObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SharedObserver(..)")
+    }
+}
@@ -0,0 +1,1103 @@
+use std::collections::VecDeque;
+
+use crate::bouquet::Bouquet;
+use crate::design::Design;
+use crate::species::{stem_total, Species, StemCount, StemTotal, Stems, SPECIES};
+
+#[derive(Debug, PartialEq)]
+pub enum StemError {
+    NotPreprocessed,
+    Parse(StemParseError),
+    /// The stock of the species already holds the most stems a stem count fits, so the
+    /// stem is not added.
+    StockFull(char),
+    /// The line already holds its capacity of stems in total, so the stem is not added.
+    Overflow(char),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StemParseError {
+    Empty,
+    UnknownSpecies(char),
+    InvalidSize(String),
+    InvalidCount(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepResult {
+    pub before: Stems,
+    pub after: Stems,
+    pub bouquet: Option<Bouquet>,
+    /// Stems grabbed in excess of the design total and returned to the stock.
+    pub returned: Stems,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub stems: u32,
+    pub bouquets: u32,
+    /// Stems refused by their line, e.g. past its capacity, which count as stems too.
+    pub rejected: u32,
+    /// Stems left in stock at the end of the run.
+    pub leftover: u32,
+}
+
+/// How the designs of a line use each species, indexed by species.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coverage {
+    /// The amount of designs using the species.
+    pub designs: [usize; SPECIES],
+    /// The largest maximum of the species in any design.
+    pub max_stems: [StemCount; SPECIES],
+}
+impl Coverage {
+    /// The species that no design uses.
+    pub fn unused(&self) -> Vec<char> {
+        Species::all()
+            .filter(|species| self.designs[species.index()] == 0)
+            .map(Species::to_char)
+            .collect()
+    }
+}
+
+/// Optional callback. Clones don't carry the callback over, so simulations on a cloned
+/// production line don't trigger it.
+pub(crate) struct Hook<F: ?Sized>(pub(crate) Option<Box<F>>);
+impl<F: ?Sized> Default for Hook<F> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+impl<F: ?Sized> Clone for Hook<F> {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+impl<F: ?Sized> std::fmt::Debug for Hook<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "Hook(Some(..))"),
+            None => write!(f, "Hook(None)"),
+        }
+    }
+}
+
+/// How `preprocess` orders designs with the same total.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TieBreak {
+    /// Designs are tried by name, and designs with the same name in registration order.
+    #[default]
+    Name,
+    /// The design registered first is tried first.
+    Registration,
+    /// An order derived from a hash of the catalog and the seed, which is the same for
+    /// the same catalog and seed. Changing the seed rotates which design is tried first.
+    Hashed { seed: u64 },
+}
+
+/// What `add_design` does with a design that is already registered, with the same name,
+/// size, minima, maxima and total.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Duplicates {
+    /// The duplicate is registered as a design of its own, so it is made as often as
+    /// the two designs together.
+    #[default]
+    Keep,
+    /// The duplicate is merged into the registered design.
+    Merge,
+    /// The duplicate is refused with `RegisterError::Duplicate`.
+    Reject,
+}
+
+/// Which design a stem makes a bouquet of, when it completes several.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// The design with the smallest total, in `preprocess` order.
+    #[default]
+    SimplestFirst,
+    /// The design that leaves the fewest stems in stock, which is the design with the
+    /// largest total. Designs with the same total are tried in `preprocess` order.
+    MinimizeExcess,
+    /// The design that can use the most stems of the species that arrived, to clear a
+    /// glut of it. Designs with the same maximum are tried in `preprocess` order.
+    ConsumeArriving,
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// FNV-1a, which unlike the std hashers is stable across Rust versions.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Logs why the design can't be made from `stock`.
+#[cfg(feature = "log")]
+fn trace_skipped(design: &Design, stock: Stems) {
+    let grabbed_stems = stock.min(design.max_stems);
+    let below_min = grabbed_stems.lanes_lt(design.min_stems);
+    if stem_total(grabbed_stems) < design.total {
+        log::trace!(
+            "design {} skipped: grabbed {} of {} stems",
+            design.name,
+            stem_total(grabbed_stems),
+            design.total
+        );
+    } else {
+        log::trace!(
+            "design {} skipped: below the minimum of {}",
+            design.name,
+            Species::from_index(below_min.trailing_zeros() as usize)
+        );
+    }
+}
+
+/// A design, as its position in the registration order of a production line. Unlike a
+/// bare `usize` it can't be mixed up with a species.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DesignId(usize);
+impl DesignId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// The lookup tables of a preprocessed production line, for restoring a line without
+/// preprocessing again.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DesignIndex {
+    designs_per_stem: Vec<Vec<DesignId>>,
+    max_per_stem: Vec<StemCount>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct LineStateRef<'a> {
+    stems: Stems,
+    designs: &'a [Design],
+}
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct LineState {
+    stems: Stems,
+    designs: Vec<Design>,
+}
+/// A production line is serialized as its stock and its registered designs. The lookup
+/// tables are rebuilt when it is deserialized, and the other settings are left at their
+/// defaults.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ProductionLine {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        LineStateRef {
+            stems: self.stems,
+            designs: &self.designs,
+        }
+        .serialize(serializer)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ProductionLine {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = LineState::deserialize(deserializer)?;
+        let mut production_line = ProductionLine::default();
+        for design in state.designs {
+            production_line
+                .add_design(design)
+                .map_err(|error| serde::de::Error::custom(format!("{error:?}")))?;
+        }
+        production_line.stems = state.stems;
+        Ok(production_line)
+    }
+}
+
+/// The stock, designs and production of a line, without its settings.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct LineSnapshot {
+    stems: Stems,
+    designs: Vec<Design>,
+    produced: Vec<u32>,
+    preprocessed: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProductionLine {
+    pub(crate) stems: Stems,
+    pub(crate) designs: Vec<Design>,
+    /// The designs using each species.
+    pub(crate) designs_per_stem: [Vec<DesignId>; SPECIES],
+    pub(crate) max_per_stem: [StemCount; SPECIES],
+    pub(crate) preprocessed: bool,
+    pub(crate) strict: bool,
+    pub(crate) arrivals: [u32; SPECIES],
+    /// Bouquets made per design.
+    pub(crate) produced: Vec<u32>,
+    /// `(before, after)` pairs of design names: `after` is only produced once at least
+    /// one `before` has been made.
+    pub(crate) precedence: Vec<(char, char)>,
+    pub(crate) on_depleted: Hook<dyn FnMut(char) + Send>,
+    /// Species whose last stem was used since the last `take_depleted`, if tracked.
+    pub(crate) depleted: Option<Vec<char>>,
+    pub(crate) paused: bool,
+    pub(crate) revenue: u64,
+    /// Only designs in these groups are made, or all designs if `None`. Designs without
+    /// a group are always made.
+    pub(crate) active_groups: Option<Vec<String>>,
+    /// The amount of stems added so far, used as the arrival time of stems.
+    pub(crate) clock: u64,
+    /// Arrival times of the stems in stock per species, oldest first. Only tracked once
+    /// a design has a `max_age`.
+    pub(crate) ages: Option<Box<[VecDeque<u64>; SPECIES]>>,
+    pub(crate) tie_break: TieBreak,
+    pub(crate) strategy: Strategy,
+    /// The most stems the line holds in total, or unbounded if `None`.
+    pub(crate) capacity: Option<StemTotal>,
+    pub(crate) duplicates: Duplicates,
+    /// The species that excess stems are returned from first, in this order.
+    pub(crate) trim_order: Vec<Species>,
+}
+impl Default for ProductionLine {
+    fn default() -> Self {
+        Self {
+            stems: Stems::splat(0),
+            designs: Vec::new(),
+            designs_per_stem: std::array::from_fn(|_| Vec::new()),
+            max_per_stem: [0; SPECIES],
+            preprocessed: false,
+            strict: false,
+            arrivals: [0; SPECIES],
+            produced: Vec::new(),
+            precedence: Vec::new(),
+            on_depleted: Hook::default(),
+            depleted: None,
+            paused: false,
+            revenue: 0,
+            active_groups: None,
+            clock: 0,
+            ages: None,
+            tie_break: TieBreak::default(),
+            strategy: Strategy::default(),
+            capacity: None,
+            duplicates: Duplicates::default(),
+            trim_order: Vec::new(),
+        }
+    }
+}
+impl ProductionLine {
+    /// Exports the lookup tables, after preprocessing if needed.
+    pub fn export_index(&mut self) -> DesignIndex {
+        if !self.preprocessed {
+            self.preprocess();
+        }
+        DesignIndex {
+            designs_per_stem: self.designs_per_stem.to_vec(),
+            max_per_stem: self.max_per_stem.to_vec(),
+        }
+    }
+    /// Imports lookup tables exported from a line with the same designs registered in
+    /// the same order, replacing the need to preprocess. The index must come from a
+    /// build with the same alphabet.
+    pub fn import_index(&mut self, index: DesignIndex) {
+        for (designs, imported) in self.designs_per_stem.iter_mut().zip(index.designs_per_stem) {
+            *designs = imported;
+        }
+        self.max_per_stem.copy_from_slice(&index.max_per_stem);
+        self.preprocessed = true;
+    }
+    pub(crate) fn snapshot(&self) -> LineSnapshot {
+        LineSnapshot {
+            stems: self.stems,
+            designs: self.designs.clone(),
+            produced: self.produced.clone(),
+            preprocessed: self.preprocessed,
+        }
+    }
+    /// Replaces the stock, designs and production with `snapshot`, rebuilding the lookup
+    /// tables. Stems in stock count as arrived at the restore. Fails if a design of the
+    /// snapshot is refused, e.g. a duplicate with `Duplicates::Reject`.
+    pub(crate) fn restore(&mut self, snapshot: &LineSnapshot) -> Result<(), RegisterError> {
+        self.designs.clear();
+        self.produced.clear();
+        for designs in self.designs_per_stem.iter_mut() {
+            designs.clear();
+        }
+        self.max_per_stem = [0; SPECIES];
+        for design in &snapshot.designs {
+            self.add_design(design.clone())?;
+        }
+        self.stems = snapshot.stems;
+        self.revenue = self
+            .designs
+            .iter()
+            .zip(&snapshot.produced)
+            .map(|(design, produced)| u64::from(design.price) * u64::from(*produced))
+            .sum();
+        self.produced = snapshot.produced.clone();
+        self.ages = None;
+        if self.designs.iter().any(|design| design.max_age.is_some()) {
+            let (stems, clock) = (self.stems, self.clock);
+            self.ages = Some(Box::new(std::array::from_fn(|stem_index| {
+                VecDeque::from(vec![clock; usize::from(stems[stem_index])])
+            })));
+        }
+        if snapshot.preprocessed {
+            self.preprocess();
+        }
+        Ok(())
+    }
+    /// Approximate bytes used by the line, including the heap allocations it owns.
+    pub fn memory_footprint(&self) -> usize {
+        let groups = self
+            .designs
+            .iter()
+            .filter_map(|design| design.group.as_ref())
+            .chain(self.active_groups.iter().flatten())
+            .map(String::capacity)
+            .sum::<usize>();
+        let active_groups = self.active_groups.as_ref().map_or(0, |active_groups| {
+            active_groups.capacity() * size_of::<String>()
+        });
+        let ages = self.ages.as_ref().map_or(0, |ages| {
+            size_of::<[VecDeque<u64>; SPECIES]>()
+                + ages.iter().map(VecDeque::capacity).sum::<usize>() * size_of::<u64>()
+        });
+        let designs_per_stem = self
+            .designs_per_stem
+            .iter()
+            .map(Vec::capacity)
+            .sum::<usize>();
+        size_of::<Self>()
+            + self.designs.capacity() * size_of::<Design>()
+            + designs_per_stem * size_of::<DesignId>()
+            + self.produced.capacity() * size_of::<u32>()
+            + self.precedence.capacity() * size_of::<(char, char)>()
+            + active_groups
+            + groups
+            + ages
+    }
+    /// Sets the maximum age of the stems used for a design, measured in stems added to
+    /// this line since their arrival. Stems in stock before the first maximum age is set
+    /// count as arrived at the start.
+    pub fn set_max_age(&mut self, name: char, max_age: Option<u64>) {
+        if max_age.is_some() && self.ages.is_none() {
+            let stems = self.stems;
+            self.ages = Some(Box::new(std::array::from_fn(|stem_index| {
+                VecDeque::from(vec![0; usize::from(stems[stem_index])])
+            })));
+        }
+        if let Some(design) = self.design_mut(name) {
+            design.max_age = max_age;
+        }
+    }
+    /// Registers the design, or with `Duplicates::Merge` returns the registered design it
+    /// duplicates.
+    pub fn add_design(&mut self, design: Design) -> Result<DesignId, RegisterError> {
+        if self.duplicates != Duplicates::Keep {
+            let duplicate = self.designs.iter().position(|registered| {
+                registered.name == design.name
+                    && registered.size == design.size
+                    && registered.total == design.total
+                    && registered.min_stems == design.min_stems
+                    && registered.max_stems == design.max_stems
+            });
+            match (duplicate, self.duplicates) {
+                (Some(design_index), Duplicates::Merge) => return Ok(DesignId(design_index)),
+                (Some(_), _) => return Err(RegisterError::Duplicate(design.name)),
+                (None, _) => {}
+            }
+        }
+        let design_id = DesignId(self.designs.len());
+        for (stem_index, amount) in design.max_stems.as_array().iter().enumerate() {
+            if *amount != 0 {
+                self.max_per_stem[stem_index] =
+                    StemCount::max(self.max_per_stem[stem_index], *amount);
+                self.designs_per_stem[stem_index].push(design_id);
+            }
+        }
+        self.designs.push(design);
+        self.produced.push(0);
+        self.preprocessed = false;
+        Ok(design_id)
+    }
+    /// Orders the designs of every species by priority. Designs added later are tried
+    /// in priority order from the next `preprocess`, which is also run by the next
+    /// `add_stem`. Preprocessing again without changes keeps the same order.
+    pub fn preprocess(&mut self) {
+        let tie_breaks: Vec<u64> = match self.tie_break {
+            TieBreak::Name => self
+                .designs
+                .iter()
+                .map(|design| u64::from(design.name))
+                .collect(),
+            // The rows may already be sorted by another tie-break, so the registration
+            // order is not kept by the stable sort alone.
+            TieBreak::Registration => (0..self.designs.len() as u64).collect(),
+            TieBreak::Hashed { seed } => {
+                let fingerprint = self.fingerprint();
+                self.designs
+                    .iter()
+                    .map(|design| fnv1a(fingerprint ^ seed, &u32::from(design.name).to_le_bytes()))
+                    .collect()
+            }
+        };
+        // @Optimization - Sort the designs per stem array by total amount of stems to
+        // prioritize simpler designs.
+        for designs in self.designs_per_stem.iter_mut() {
+            designs.sort_by_key(|design_id| {
+                (self.designs[design_id.0].total, tie_breaks[design_id.0])
+            });
+        }
+        self.preprocessed = true;
+    }
+    /// Preprocesses like `preprocess`, and reports how the designs cover the species.
+    pub fn preprocess_with_report(&mut self) -> Coverage {
+        self.preprocess();
+        Coverage {
+            designs: std::array::from_fn(|stem_index| self.designs_per_stem[stem_index].len()),
+            max_stems: self.max_per_stem,
+        }
+    }
+    /// Hash of the registered designs, which is the same for the same catalog.
+    fn fingerprint(&self) -> u64 {
+        self.designs.iter().fold(FNV_OFFSET_BASIS, |hash, design| {
+            let hash = fnv1a(hash, &u32::from(design.name).to_le_bytes());
+            let hash = fnv1a(hash, &design.total.to_le_bytes());
+            design
+                .max_stems
+                .as_array()
+                .iter()
+                .fold(hash, |hash, max| fnv1a(hash, &max.to_le_bytes()))
+        })
+    }
+    /// Makes at most `quota` bouquets of the design with this name, or any amount if
+    /// `None`. Once the quota is reached, its stems are left for the other designs.
+    pub fn set_quota(&mut self, name: char, quota: Option<u32>) {
+        if let Some(design) = self.design_mut(name) {
+            design.quota = quota;
+        }
+    }
+    pub fn set_strategy(&mut self, strategy: Strategy) {
+        self.strategy = strategy;
+    }
+    /// Limits the stems in stock to `capacity` in total. A stem that arrives when the
+    /// line is full is refused with `StemError::Overflow`.
+    pub fn set_capacity(&mut self, capacity: Option<StemTotal>) {
+        self.capacity = capacity;
+    }
+    /// When a bouquet grabbed more stems than its total, the excess is returned from
+    /// these species first, in this order, down to their minimum. The rest is returned
+    /// in proportion to the surplus of every species, which is all of the excess with an
+    /// empty order.
+    pub fn set_trim_order(&mut self, trim_order: Vec<Species>) {
+        self.trim_order = trim_order;
+    }
+    /// Applies to the designs added from now on.
+    pub fn set_duplicates(&mut self, duplicates: Duplicates) {
+        self.duplicates = duplicates;
+    }
+    pub fn set_tie_break(&mut self, tie_break: TieBreak) {
+        self.tie_break = tie_break;
+        self.preprocessed = false;
+    }
+    pub fn add_stem(&mut self, species: Species) -> Result<Option<Bouquet>, StemError> {
+        Ok(self
+            .add_stem_with_excess(species)?
+            .map(|(bouquet, _)| bouquet))
+    }
+    /// Adds a stem like `add_stem`, and if that makes a bouquet continues making
+    /// bouquets from the stock in the same pass. Excess returned by a bouquet is back in
+    /// stock, so it can be used by the bouquets that follow.
+    pub fn add_stem_cascading(&mut self, species: Species) -> Result<Vec<Bouquet>, StemError> {
+        let mut bouquets: Vec<Bouquet> = self.add_stem(species)?.into_iter().collect();
+        if !bouquets.is_empty() {
+            bouquets.extend(self.drain());
+        }
+        Ok(bouquets)
+    }
+    /// Adds a stem like `add_stem`, and reports the full change in stock.
+    pub fn step(&mut self, species: Species) -> Result<StepResult, StemError> {
+        let before = self.stems;
+        let (bouquet, returned) = match self.add_stem_with_excess(species)? {
+            Some((bouquet, returned)) => (Some(bouquet), returned),
+            None => (None, Stems::splat(0)),
+        };
+        Ok(StepResult {
+            before,
+            after: self.stems,
+            bouquet,
+            returned,
+        })
+    }
+    /// Adds a stem, returning the produced bouquet together with the excess stems that
+    /// were grabbed for it and then returned to the stock.
+    fn add_stem_with_excess(
+        &mut self,
+        species: Species,
+    ) -> Result<Option<(Bouquet, Stems)>, StemError> {
+        self.admit(species)?;
+        self.arrivals[species.index()] = self.arrivals[species.index()].saturating_add(1);
+        self.stems[species] += 1;
+        self.clock += 1;
+        if let Some(ages) = &mut self.ages {
+            ages[species.index()].push_back(self.clock);
+        }
+        #[cfg(feature = "log")]
+        log::trace!("stem {species}, {} in stock", self.stems[species]);
+        if self.paused {
+            return Ok(None);
+        }
+        if self.stems[species] > self.max_per_stem[species.index()] && self.skips_past_max() {
+            // @Optimization - If we already surpassed the max required numbers of stems
+            // of this species for all designs, we can't make a new design this round.
+            #[cfg(feature = "log")]
+            log::trace!("stem {species} exceeds the maximum of every design");
+            return Ok(None);
+        }
+        Ok(self.try_make_design_with_excess(species))
+    }
+    /// Checks that a stem of the species can be added, preprocessing first if needed.
+    fn admit(&mut self, species: Species) -> Result<(), StemError> {
+        if !self.preprocessed {
+            // Matching on unsorted designs still works, but silently loses the
+            // simplest-first priority. Strict mode makes this mistake visible.
+            if self.strict {
+                return Err(StemError::NotPreprocessed);
+            }
+            self.preprocess();
+        }
+        if self.stems[species] == StemCount::MAX {
+            return Err(StemError::StockFull(species.to_char()));
+        }
+        if let Some(capacity) = self.capacity {
+            if self.stock_total() >= capacity {
+                return Err(StemError::Overflow(species.to_char()));
+            }
+        }
+        Ok(())
+    }
+    /// Whether a stem of a species of which the stock exceeds the maximum of every design
+    /// can be skipped. A design held back by a precedence may have been released since
+    /// the last stem, and with maximum ages the new stem replaces an expired one, so then
+    /// the designs are checked anyway.
+    fn skips_past_max(&self) -> bool {
+        self.precedence.is_empty() && self.ages.is_none()
+    }
+    /// The bouquet that `add_stem` would make of a stem of the species, without changing
+    /// the stock. `commit_add_stem` then makes exactly this bouquet.
+    pub fn peek_add_stem(&mut self, species: Species) -> Option<Bouquet> {
+        self.admit(species).ok()?;
+        if self.paused
+            || (self.stems[species] >= self.max_per_stem[species.index()] && self.skips_past_max())
+        {
+            return None;
+        }
+        // The stem is counted for the selection only, and taken out again after.
+        self.stems[species] += 1;
+        self.clock += 1;
+        if let Some(ages) = &mut self.ages {
+            ages[species.index()].push_back(self.clock);
+        }
+        let selected = self.select_design(species);
+        self.remove_stem(species);
+        self.clock -= 1;
+        selected.map(|(design_id, taken_stems, _)| {
+            let design = &self.designs[design_id.0];
+            Bouquet {
+                name: design.name,
+                size: design.size,
+                stems: taken_stems,
+            }
+        })
+    }
+    /// Adds a stem after `peek_add_stem`, the same as `add_stem`.
+    pub fn commit_add_stem(&mut self, species: Species) -> Result<Option<Bouquet>, StemError> {
+        self.add_stem(species)
+    }
+    /// Takes a stem of the species back out of stock, e.g. to correct a mis-scan. The
+    /// bouquets already made are kept. Returns whether there was a stem to remove.
+    pub fn remove_stem(&mut self, species: Species) -> bool {
+        if self.stems[species] == 0 {
+            return false;
+        }
+        self.stems[species] -= 1;
+        if let Some(ages) = &mut self.ages {
+            ages[species.index()].pop_back();
+        }
+        true
+    }
+    /// Makes a bouquet from the stock with the first design using the species that can
+    /// be made, without adding a stem.
+    pub fn try_make_design(&mut self, species: Species) -> Option<Bouquet> {
+        if !self.preprocessed {
+            self.preprocess();
+        }
+        self.try_make_design_with_excess(species)
+            .map(|(bouquet, _)| bouquet)
+    }
+    fn try_make_design_with_excess(&mut self, species: Species) -> Option<(Bouquet, Stems)> {
+        let (design_id, taken_stems, returned_stems) = self.select_design(species)?;
+        Some(self.apply_design(design_id, taken_stems, returned_stems))
+    }
+    /// The design the stock makes a bouquet of for the species, with the stems it takes
+    /// and returns, without changing the stock.
+    fn select_design(&self, species: Species) -> Option<(DesignId, Stems, Stems)> {
+        let designs = &self.designs_per_stem[species.index()];
+        #[cfg(feature = "log")]
+        log::trace!(
+            "considering designs {:?} for stem {species}",
+            designs
+                .iter()
+                .map(|design_id| self.designs[design_id.0].name)
+                .collect::<String>()
+        );
+        // @Optimization - A design that needs more of this species than the stock holds
+        // can't be made, which a scalar comparison tells before the vector work.
+        let in_stock = self.stems[species];
+        let selected = match self.strategy {
+            Strategy::SimplestFirst => designs.iter().find_map(|design_id| {
+                if self.designs[design_id.0].min_stems[species] > in_stock {
+                    #[cfg(feature = "log")]
+                    log::trace!(
+                        "design {} skipped: below the minimum of {species}",
+                        self.designs[design_id.0].name
+                    );
+                    return None;
+                }
+                self.try_take(*design_id)
+                    .map(|(taken, returned)| (*design_id, taken, returned))
+            }),
+            Strategy::MinimizeExcess => designs
+                .iter()
+                .filter(|design_id| self.designs[design_id.0].min_stems[species] <= in_stock)
+                .filter(|design_id| {
+                    self.usable_stock(**design_id)
+                        .is_some_and(|stock| self.designs[design_id.0].can_make(stock))
+                })
+                .fold(None, |best: Option<DesignId>, design_id| match best {
+                    Some(best) if self.designs[best.0].total >= self.designs[design_id.0].total => {
+                        Some(best)
+                    }
+                    _ => Some(*design_id),
+                })
+                .and_then(|design_id| {
+                    self.try_take(design_id)
+                        .map(|(taken, returned)| (design_id, taken, returned))
+                }),
+            Strategy::ConsumeArriving => designs
+                .iter()
+                .filter(|design_id| self.designs[design_id.0].min_stems[species] <= in_stock)
+                .filter(|design_id| {
+                    self.usable_stock(**design_id)
+                        .is_some_and(|stock| self.designs[design_id.0].can_make(stock))
+                })
+                .fold(None, |best: Option<DesignId>, design_id| match best {
+                    Some(best)
+                        if self.designs[best.0].max_stems[species]
+                            >= self.designs[design_id.0].max_stems[species] =>
+                    {
+                        Some(best)
+                    }
+                    _ => Some(*design_id),
+                })
+                .and_then(|design_id| {
+                    self.try_take(design_id)
+                        .map(|(taken, returned)| (design_id, taken, returned))
+                }),
+        };
+        selected
+    }
+    /// The stock the design may use, or `None` if the design can't be made at all now.
+    fn usable_stock(&self, design_id: DesignId) -> Option<Stems> {
+        let design = &self.designs[design_id.0];
+        if design
+            .quota
+            .is_some_and(|quota| self.produced[design_id.0] >= quota)
+        {
+            return None;
+        }
+        if !self.precedence.is_empty() && !self.prerequisites_met(design.name) {
+            return None;
+        }
+        if let (Some(active_groups), Some(group)) = (&self.active_groups, &design.group) {
+            if !active_groups.contains(group) {
+                return None;
+            }
+        }
+        Some(match (design.max_age, &self.ages) {
+            // The lanes past the species are padding, without ages.
+            (Some(max_age), Some(ages)) => Stems::from_array(std::array::from_fn(|stem_index| {
+                ages.get(stem_index).map_or(0, |ages| {
+                    ages.iter()
+                        .rev()
+                        .take_while(|arrival| self.clock - **arrival <= max_age)
+                        .count() as StemCount
+                })
+            })),
+            _ => self.stems,
+        })
+    }
+    /// Makes a bouquet of the design from stock, if possible.
+    fn make_design(&mut self, design_id: DesignId) -> Option<(Bouquet, Stems)> {
+        let (taken_stems, returned_stems) = self.try_take(design_id)?;
+        Some(self.apply_design(design_id, taken_stems, returned_stems))
+    }
+    /// The stems a bouquet of the design takes from stock and returns, if it can be made.
+    fn try_take(&self, design_id: DesignId) -> Option<(Stems, Stems)> {
+        let design = &self.designs[design_id.0];
+        let Some(stock) = self.usable_stock(design_id) else {
+            #[cfg(feature = "log")]
+            log::trace!(
+                "design {} skipped: its quota, a precedence or a group",
+                design.name
+            );
+            return None;
+        };
+        let taken = design.take(stock, &self.trim_order);
+        #[cfg(feature = "log")]
+        if taken.is_none() {
+            trace_skipped(design, stock);
+        }
+        taken
+    }
+    /// Takes the stems of a bouquet of the design from stock.
+    fn apply_design(
+        &mut self,
+        design_id: DesignId,
+        taken_stems: Stems,
+        returned_stems: Stems,
+    ) -> (Bouquet, Stems) {
+        let design = &self.designs[design_id.0];
+        #[cfg(feature = "log")]
+        log::trace!("design {} made", design.name);
+        self.stems -= taken_stems;
+        if let Some(ages) = &mut self.ages {
+            for (stem_index, ages) in ages.iter_mut().enumerate() {
+                let amount = usize::from(taken_stems[stem_index]);
+                if design.max_age.is_some() {
+                    // Fresh stems are the most recent ones, older stems are kept for
+                    // designs without a maximum age.
+                    ages.truncate(ages.len() - amount);
+                } else {
+                    ages.drain(..amount);
+                }
+            }
+        }
+        if self.on_depleted.0.is_some() || self.depleted.is_some() {
+            let mut bitmask = taken_stems.nonzero() & !self.stems.nonzero();
+            while bitmask != 0 {
+                let species = Species::from_index(bitmask.trailing_zeros() as usize).to_char();
+                if let Some(on_depleted) = &mut self.on_depleted.0 {
+                    on_depleted(species);
+                }
+                if let Some(depleted) = &mut self.depleted {
+                    depleted.push(species);
+                }
+                bitmask &= bitmask - 1;
+            }
+        }
+        self.produced[design_id.0] += 1;
+        self.revenue += u64::from(design.price);
+        let bouquet = Bouquet {
+            name: design.name,
+            size: design.size,
+            stems: taken_stems,
+        };
+        (bouquet, returned_stems)
+    }
+    /// Makes bouquets from the stock until no design can be made anymore, trying the
+    /// simplest designs first.
+    pub fn drain(&mut self) -> Vec<Bouquet> {
+        let mut order: Vec<DesignId> = (0..self.designs.len()).map(DesignId).collect();
+        order.sort_by_key(|design_id| self.designs[design_id.0].total);
+        let mut bouquets = Vec::new();
+        while let Some((bouquet, _)) = order
+            .iter()
+            .find_map(|design_id| self.make_design(*design_id))
+        {
+            bouquets.push(bouquet);
+        }
+        bouquets
+    }
+    /// While paused, stems are added to the stock without making bouquets.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+    /// Resumes making bouquets, returning the bouquets that can be made from the stock
+    /// accumulated while paused.
+    pub fn resume(&mut self) -> Vec<Bouquet> {
+        self.paused = false;
+        self.drain()
+    }
+    /// Moves the design with this name to the front of the scan list of each of its
+    /// species, so it is preferred over all other designs.
+    pub fn prioritize(&mut self, name: char) {
+        if !self.preprocessed {
+            self.preprocess();
+        }
+        let design_id = match self.designs.iter().position(|design| design.name == name) {
+            Some(design_index) => DesignId(design_index),
+            None => return,
+        };
+        for designs in self.designs_per_stem.iter_mut() {
+            if let Some(position) = designs.iter().position(|id| *id == design_id) {
+                designs[..=position].rotate_right(1);
+            }
+        }
+    }
+    /// Amount of designs using the species.
+    pub fn design_count(&self, species: Species) -> usize {
+        self.designs_per_stem[species.index()].len()
+    }
+    pub fn top_consumer(&self, species: Species) -> Option<char> {
+        let mut top: Option<&Design> = None;
+        for design_id in &self.designs_per_stem[species.index()] {
+            let design = &self.designs[design_id.0];
+            if top.is_none_or(|top| design.max_stems[species] > top.max_stems[species]) {
+                top = Some(design);
+            }
+        }
+        top.map(|design| design.name)
+    }
+    /// Designs that a bouquet could be made of from the current stock, in registration
+    /// order, without taking any stems.
+    pub fn producible(&self) -> Vec<char> {
+        self.designs
+            .iter()
+            .enumerate()
+            .filter(|(design_index, design)| {
+                self.usable_stock(DesignId(*design_index))
+                    .is_some_and(|stock| design.can_make(stock))
+            })
+            .map(|(_, design)| design.name)
+            .collect()
+    }
+    /// The design that the next stem of `species` would make a bouquet of, without
+    /// adding it. The stem is added to a copy of the line, so the selection is the same
+    /// as that of `add_stem`.
+    pub fn next_design_for(&self, species: Species) -> Option<char> {
+        let mut production_line = self.clone();
+        production_line
+            .add_stem(species)
+            .ok()
+            .flatten()
+            .map(|bouquet| bouquet.name)
+    }
+    /// Designs that `add_stem` could make a bouquet of now, like `producible`, the design
+    /// of which the most bouquets could be made first. The count is capped at the rest
+    /// of the quota of the design.
+    pub fn producible_now(&self) -> Vec<char> {
+        let mut producible: Vec<(u32, char)> = self
+            .designs
+            .iter()
+            .enumerate()
+            .filter_map(|(design_index, design)| {
+                let stock = self.usable_stock(DesignId(design_index))?;
+                if !design.can_make(stock) {
+                    return None;
+                }
+                let count = design.max_bouquets(stock);
+                let count = design.quota.map_or(count, |quota| {
+                    count.min(quota - self.produced[design_index])
+                });
+                Some((count, design.name))
+            })
+            .collect();
+        producible.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+        producible.into_iter().map(|(_, name)| name).collect()
+    }
+    /// Calls `on_depleted` with the species whenever a bouquet consumes the last stem of
+    /// that species.
+    pub fn on_depleted(&mut self, on_depleted: impl FnMut(char) + Send + 'static) {
+        self.on_depleted = Hook(Some(Box::new(on_depleted)));
+    }
+    /// The species depleted since the last call, in the order they were depleted.
+    pub(crate) fn take_depleted(&mut self) -> Vec<char> {
+        self.depleted
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+    pub fn add_precedence(&mut self, before: char, after: char) {
+        self.precedence.push((before, after));
+    }
+    fn prerequisites_met(&self, name: char) -> bool {
+        self.precedence
+            .iter()
+            .filter(|(_, after)| *after == name)
+            .all(|(before, _)| self.produced_count(*before) > 0)
+    }
+    pub fn produced_count(&self, name: char) -> u32 {
+        self.designs
+            .iter()
+            .zip(self.produced.iter())
+            .find(|(design, _)| design.name == name)
+            .map_or(0, |(_, produced)| *produced)
+    }
+    /// Bouquets made per design, in registration order.
+    pub fn production(&self) -> Vec<(char, u32)> {
+        self.designs
+            .iter()
+            .zip(self.produced.iter())
+            .map(|(design, produced)| (design.name, *produced))
+            .collect()
+    }
+    /// Stems taken per design, in registration order. Excess stems returned to the stock
+    /// are not counted.
+    pub fn consumption(&self) -> Vec<(char, u32)> {
+        self.designs
+            .iter()
+            .zip(self.produced.iter())
+            .map(|(design, produced)| (design.name, produced * design.total))
+            .collect()
+    }
+    /// The stems in stock.
+    pub fn stems(&self) -> Stems {
+        self.stems
+    }
+    /// The amount of stems in stock, of all species together. Summed in a `u32`, as the
+    /// stock of every species can be full without a capacity.
+    pub fn stock_total(&self) -> u32 {
+        self.stems.as_array().iter().copied().map(u32::from).sum()
+    }
+    /// The stock as a percentage of the capacity, if the line has one.
+    pub fn utilization(&self) -> Option<f32> {
+        self.capacity
+            .map(|capacity| self.stock_total() as f32 * 100.0 / capacity as f32)
+    }
+    /// Total stems received per species, regardless of whether they were consumed.
+    pub fn arrivals(&self) -> &[u32; SPECIES] {
+        &self.arrivals
+    }
+    /// The designs that need a species of which no stem arrived, with those species.
+    pub fn starved_designs(&self) -> Vec<(char, Vec<char>)> {
+        self.designs
+            .iter()
+            .filter_map(|design| {
+                let missing: Vec<char> = Species::all()
+                    .filter(|species| {
+                        design.min_stems[*species] != 0 && self.arrivals[species.index()] == 0
+                    })
+                    .map(Species::to_char)
+                    .collect();
+                (!missing.is_empty()).then_some((design.name, missing))
+            })
+            .collect()
+    }
+    /// The stems per species of one bouquet of every design. The sums are taken in
+    /// `u32`, as the bouquets of many large designs add up to more than a stem count
+    /// holds, and saturate at `u32::MAX`.
+    pub fn min_stock_for_full_catalog(&self) -> [u32; SPECIES] {
+        let mut stock = [0u32; SPECIES];
+        for design in self.designs.iter() {
+            for (stock, amount) in stock.iter_mut().zip(design.sample_bouquet().as_array()) {
+                *stock = stock.saturating_add(u32::from(*amount));
+            }
+        }
+        stock
+    }
+    /// The sum of the maximum per species over all designs.
+    pub fn total_demand(&self) -> [u32; SPECIES] {
+        let mut demand = [0; SPECIES];
+        for design in self.designs.iter() {
+            for (demand, max) in demand.iter_mut().zip(design.max_stems.as_array()) {
+                *demand += u32::from(*max);
+            }
+        }
+        demand
+    }
+    pub fn dominated_designs(&self) -> Vec<(char, char)> {
+        let mut dominated = Vec::new();
+        for design in self.designs.iter() {
+            let species = design.max_stems.nonzero();
+            for other in self.designs.iter() {
+                if design.total > other.total
+                    || species != other.max_stems.nonzero()
+                    || design.max_stems.lanes_gt(other.max_stems) != 0
+                {
+                    continue;
+                }
+                if design.total == other.total && design.max_stems == other.max_stems {
+                    // Identical constraints (this includes the design itself).
+                    continue;
+                }
+                dominated.push((design.name, other.name));
+            }
+        }
+        dominated
+    }
+    pub fn design(&self, name: char) -> Option<&Design> {
+        self.designs.iter().find(|design| design.name == name)
+    }
+    pub fn design_mut(&mut self, name: char) -> Option<&mut Design> {
+        self.designs.iter_mut().find(|design| design.name == name)
+    }
+    pub fn designs(&self) -> impl Iterator<Item = &Design> {
+        self.designs.iter()
+    }
+    /// Total price of all bouquets made.
+    pub fn revenue(&self) -> u64 {
+        self.revenue
+    }
+    /// Whether designs `a` and `b` together need more of a species they share than
+    /// `stock` holds, so that making one precludes making the other.
+    pub fn conflict(&self, a: char, b: char, stock: Stems) -> bool {
+        let (a, b) = match (self.design(a), self.design(b)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return false,
+        };
+        (0..SPECIES).any(|stem_index| {
+            a.max_stems[stem_index] != 0
+                && b.max_stems[stem_index] != 0
+                && StemTotal::from(a.min_stems[stem_index])
+                    + StemTotal::from(b.min_stems[stem_index])
+                    > StemTotal::from(stock[stem_index])
+        })
+    }
+    /// The design closest to the stems of `bouquet`, with the distance: the amount of
+    /// stems per species outside of the design's range, plus the difference in total.
+    pub fn nearest_design(&self, bouquet: &Bouquet) -> Option<(char, u32)> {
+        let bouquet_amount = stem_total(bouquet.stems);
+        self.designs
+            .iter()
+            .map(|design| {
+                let outside = design.min_stems.saturating_sub(bouquet.stems)
+                    + bouquet.stems.saturating_sub(design.max_stems);
+                let distance = outside
+                    .as_array()
+                    .iter()
+                    .map(|amount| u32::from(*amount))
+                    .sum::<u32>()
+                    + bouquet_amount.abs_diff(design.total);
+                (design.name, distance)
+            })
+            .min_by_key(|(_, distance)| *distance)
+    }
+    pub fn best_completion(&self, partial: Stems) -> Option<char> {
+        let partial_amount = stem_total(partial);
+        let mut best: Option<(StemTotal, char)> = None;
+        for design in self.designs.iter() {
+            if partial_amount > design.total || partial.lanes_gt(design.max_stems) != 0 {
+                // The partial already holds stems this design can't use.
+                continue;
+            }
+            let missing_amount = design.total - partial_amount;
+            let missing_min = stem_total(design.min_stems.saturating_sub(partial));
+            let needed = StemTotal::max(missing_amount, missing_min);
+            if best.is_none_or(|(best_needed, _)| needed < best_needed) {
+                best = Some((needed, design.name));
+            }
+        }
+        best.map(|(_, name)| name)
+    }
+}
+
+/// Reasons a production line refuses to register a design.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RegisterError {
+    /// The design with this name is already registered, with `Duplicates::Reject`.
+    Duplicate(char),
+}
@@ -1,9 +1,31 @@
 use std::default::Default;
+use std::ops::{Index, IndexMut};
 
-#[derive(Clone, Debug)]
+/// The amount of sizes.
+pub const SIZES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Size {
     Small,
     Large,
+    ExtraLarge,
+}
+impl Size {
+    /// Every size, in the order of their values in a `SizeMap`.
+    pub const ALL: [Size; SIZES] = [Size::Small, Size::Large, Size::ExtraLarge];
+    /// The numeric code of the size, which is also its place in `Size::ALL`.
+    pub fn to_index(self) -> usize {
+        match self {
+            Size::Small => 0,
+            Size::Large => 1,
+            Size::ExtraLarge => 2,
+        }
+    }
+    /// The size with the numeric code `index`, if any.
+    pub fn from_index(index: usize) -> Option<Size> {
+        Size::ALL.get(index).copied()
+    }
 }
 impl std::str::FromStr for Size {
     type Err = String;
@@ -11,6 +33,7 @@ impl std::str::FromStr for Size {
         match input {
             "S" => Ok(Size::Small),
             "L" => Ok(Size::Large),
+            "XL" => Ok(Size::ExtraLarge),
             _ => Err(format!("Invalid size: {input}")),
         }
     }
@@ -20,28 +43,58 @@ impl std::fmt::Display for Size {
         match self {
             Size::Small => write!(f, "S"),
             Size::Large => write!(f, "L"),
+            Size::ExtraLarge => write!(f, "XL"),
         }
     }
 }
 
-#[derive(Debug)]
+/// A value per size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SizeMap<T> {
-    pub small: T,
-    pub large: T,
+    values: [T; SIZES],
 }
 impl<T> SizeMap<T> {
-    pub fn new(small: T, large: T) -> Self {
-        Self { small, large }
-    }
-    pub fn get_mut(&mut self, size: &Size) -> &mut T {
-        match size {
-            Size::Small => &mut self.small,
-            Size::Large => &mut self.large,
+    /// Makes the value of every size with `f`.
+    pub fn from_fn(mut f: impl FnMut(Size) -> T) -> Self {
+        Self {
+            values: std::array::from_fn(|index| f(Size::ALL[index])),
         }
     }
+    pub fn get(&self, size: Size) -> &T {
+        &self.values[size.to_index()]
+    }
+    pub fn get_mut(&mut self, size: Size) -> &mut T {
+        &mut self.values[size.to_index()]
+    }
+    /// The values in the order of `Size::ALL`.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.values.iter()
+    }
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.values.iter_mut()
+    }
+    /// The sizes with their values, in the order of `Size::ALL`.
+    pub fn iter(&self) -> impl Iterator<Item = (Size, &T)> {
+        Size::ALL.into_iter().zip(self.values.iter())
+    }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Size, &mut T)> {
+        Size::ALL.into_iter().zip(self.values.iter_mut())
+    }
+}
+impl<T> Index<Size> for SizeMap<T> {
+    type Output = T;
+    fn index(&self, size: Size) -> &T {
+        self.get(size)
+    }
+}
+impl<T> IndexMut<Size> for SizeMap<T> {
+    fn index_mut(&mut self, size: Size) -> &mut T {
+        self.get_mut(size)
+    }
 }
 impl<T: Default> Default for SizeMap<T> {
     fn default() -> Self {
-        Self::new(T::default(), T::default())
+        Self::from_fn(|_| T::default())
     }
 }
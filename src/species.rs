@@ -0,0 +1,193 @@
+use std::fmt;
+
+use crate::production_line::StemParseError;
+use crate::size::Size;
+use crate::stem_vec::StemVec;
+
+/// Element type of the stem vectors. Building with the `u8-stems` feature halves the
+/// memory used per vector, when all design maxima fit in a `u8`.
+#[cfg(not(feature = "u8-stems"))]
+pub type StemCount = u16;
+#[cfg(feature = "u8-stems")]
+pub type StemCount = u8;
+
+/// Sums across lanes, wide enough that the full lanes of any stem count add up without
+/// overflowing.
+pub type StemTotal = u32;
+
+const _: () = assert!(StemCount::MAX as u64 * LANES as u64 <= StemTotal::MAX as u64);
+
+/// The amount of species, `a`-`z`. Building with the `wide-alphabet` feature adds the
+/// 32 lowercase Latin-1 letters `ß`-`ÿ` (without `÷`), for 58 species. Building with
+/// the `uppercase-species` feature adds `A`-`Z`, for 52 species. Both features together
+/// make 84 species, with `A`-`Z` after `ß`-`ÿ`.
+pub const SPECIES: usize = LETTERS + LATIN1_LETTERS + UPPERCASE_LETTERS;
+
+/// The letters `a`-`z`, which are the first species of every alphabet.
+const LETTERS: usize = 26;
+/// The lowercase Latin-1 letters `ß`-`ÿ`, without `÷`.
+#[cfg(feature = "wide-alphabet")]
+const LATIN1_LETTERS: usize = 32;
+#[cfg(not(feature = "wide-alphabet"))]
+const LATIN1_LETTERS: usize = 0;
+/// The uppercase letters `A`-`Z`, which follow every other species.
+#[cfg(feature = "uppercase-species")]
+const UPPERCASE_LETTERS: usize = LETTERS;
+#[cfg(not(feature = "uppercase-species"))]
+const UPPERCASE_LETTERS: usize = 0;
+
+/// The lane width of the stem vectors, the smallest supported width that fits every
+/// species. Building with the `lanes-64` feature always uses 64 lanes, for targets on
+/// which the wider vectors are faster. The 84 species of `wide-alphabet` together with
+/// `uppercase-species` take 128 lanes.
+#[cfg(not(any(
+    feature = "wide-alphabet",
+    feature = "uppercase-species",
+    feature = "lanes-64"
+)))]
+pub const LANES: usize = 32;
+#[cfg(all(
+    any(
+        feature = "wide-alphabet",
+        feature = "uppercase-species",
+        feature = "lanes-64"
+    ),
+    not(all(feature = "wide-alphabet", feature = "uppercase-species"))
+))]
+pub const LANES: usize = 64;
+#[cfg(all(feature = "wide-alphabet", feature = "uppercase-species"))]
+pub const LANES: usize = 128;
+
+const _: () = assert!(LANES >= SPECIES);
+
+/// The species as a regex character class.
+#[cfg(not(any(feature = "wide-alphabet", feature = "uppercase-species")))]
+pub(crate) const SPECIES_CLASS: &str = "[a-z]";
+#[cfg(all(feature = "wide-alphabet", not(feature = "uppercase-species")))]
+pub(crate) const SPECIES_CLASS: &str = "[a-zß-öø-ÿ]";
+#[cfg(all(feature = "uppercase-species", not(feature = "wide-alphabet")))]
+pub(crate) const SPECIES_CLASS: &str = "[a-zA-Z]";
+#[cfg(all(feature = "wide-alphabet", feature = "uppercase-species"))]
+pub(crate) const SPECIES_CLASS: &str = "[a-zA-Zß-öø-ÿ]";
+
+pub type Stems = StemVec;
+
+pub fn stem_total(stems: Stems) -> StemTotal {
+    stems.total()
+}
+
+pub fn is_species(value: char) -> bool {
+    match value {
+        'a'..='z' => true,
+        #[cfg(feature = "wide-alphabet")]
+        'ß'..='ÿ' => value != '÷',
+        #[cfg(feature = "uppercase-species")]
+        'A'..='Z' => true,
+        _ => false,
+    }
+}
+/// A species, as the lane of its stems in the stem vectors. Unlike a bare `usize` it
+/// can't be mixed up with the index of a design.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Species(u8);
+impl Species {
+    /// The species of `value`, or `None` if it isn't a species.
+    pub fn from_char(value: char) -> Option<Species> {
+        char_to_stem_index(value)
+    }
+    pub fn to_char(self) -> char {
+        stem_index_to_char(self)
+    }
+    /// The lane of the species in the stem vectors.
+    pub fn index(self) -> usize {
+        usize::from(self.0)
+    }
+    pub(crate) fn from_index(index: usize) -> Species {
+        debug_assert!(index < SPECIES);
+        Species(index as u8)
+    }
+    /// Every species, in lane order.
+    pub fn all() -> impl Iterator<Item = Species> {
+        (0..SPECIES).map(Species::from_index)
+    }
+}
+impl TryFrom<char> for Species {
+    type Error = StemParseError;
+    fn try_from(value: char) -> Result<Species, StemParseError> {
+        Species::from_char(value).ok_or(StemParseError::UnknownSpecies(value))
+    }
+}
+impl From<Species> for char {
+    fn from(species: Species) -> char {
+        species.to_char()
+    }
+}
+impl fmt::Display for Species {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+/// The species of `value`, or `None` if it isn't a species, e.g. `'@'`, `'{'` or `'€'`.
+pub fn char_to_stem_index(value: char) -> Option<Species> {
+    const LOWER_BOUND: usize = 'a' as usize;
+    if !is_species(value) {
+        return None;
+    }
+    #[cfg(feature = "wide-alphabet")]
+    if value >= 'ß' {
+        // '÷' sits between 'ö' and 'ø'.
+        let index = LETTERS + value as usize - 'ß' as usize;
+        return Some(Species::from_index(if value > '÷' {
+            index - 1
+        } else {
+            index
+        }));
+    }
+    #[cfg(feature = "uppercase-species")]
+    if value.is_ascii_uppercase() {
+        return Some(Species::from_index(
+            SPECIES - UPPERCASE_LETTERS + value as usize - 'A' as usize,
+        ));
+    }
+    Some(Species::from_index(value as usize - LOWER_BOUND))
+}
+pub fn stem_index_to_char(species: Species) -> char {
+    const LOWER_BOUND: usize = 'a' as usize;
+    let stem_index = species.index();
+    #[cfg(feature = "uppercase-species")]
+    if stem_index >= SPECIES - UPPERCASE_LETTERS {
+        let value = 'A' as usize + stem_index - (SPECIES - UPPERCASE_LETTERS);
+        return char::from_u32(value as u32).unwrap();
+    }
+    #[cfg(feature = "wide-alphabet")]
+    if stem_index >= LETTERS {
+        let value = 'ß' as usize + stem_index - LETTERS;
+        let value = if value >= '÷' as usize {
+            value + 1
+        } else {
+            value
+        };
+        return char::from_u32(value as u32).unwrap();
+    }
+    char::from_u32((LOWER_BOUND + stem_index) as u32).unwrap()
+}
+pub fn stems_to_vec(stems: Stems) -> Vec<(char, StemCount)> {
+    Species::all()
+        .filter(|species| stems[*species] != 0)
+        .map(|species| (species.to_char(), stems[species]))
+        .collect()
+}
+/// The stems of a list of species and amounts, or the first value that isn't a species.
+pub fn vec_to_stems(stems: &[(char, StemCount)]) -> Result<Stems, StemParseError> {
+    let mut vector = Stems::splat(0);
+    for (species, amount) in stems {
+        vector[Species::try_from(*species)?] += amount;
+    }
+    Ok(vector)
+}
+/// The stem-phase input line for a stem, e.g. `aL`.
+pub fn stem_line(species: char, size: Size) -> String {
+    format!("{species}{size}")
+}
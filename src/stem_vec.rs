@@ -0,0 +1,235 @@
+//! A vector with a stem count per species. With the `simd` feature it is a portable SIMD
+//! vector, which needs nightly Rust; without it a plain array, which builds on stable.
+//! Portable SIMD has at most 64 lanes, so the 128 lanes of `wide-alphabet` together with
+//! `uppercase-species` use the plain array as well.
+//!
+//! Lane comparisons return a `LaneMask` with a bit per lane, lane 0 in the lowest bit.
+
+use std::ops::{Add, AddAssign, BitOr, Div, Index, IndexMut, Sub, SubAssign};
+#[cfg(all(
+    feature = "simd",
+    not(all(feature = "wide-alphabet", feature = "uppercase-species"))
+))]
+use std::simd::{
+    cmp::{SimdOrd, SimdPartialEq, SimdPartialOrd},
+    num::SimdUint,
+    Mask, Select, Simd, SimdElement,
+};
+
+use crate::species::{Species, StemCount, StemTotal, LANES};
+
+#[cfg(all(
+    feature = "simd",
+    not(all(feature = "wide-alphabet", feature = "uppercase-species"))
+))]
+type Lanes = Simd<StemCount, LANES>;
+#[cfg(not(all(
+    feature = "simd",
+    not(all(feature = "wide-alphabet", feature = "uppercase-species"))
+)))]
+type Lanes = [StemCount; LANES];
+
+/// A bit per lane, as returned by the lane comparisons.
+#[cfg(not(all(feature = "wide-alphabet", feature = "uppercase-species")))]
+pub type LaneMask = u64;
+#[cfg(all(feature = "wide-alphabet", feature = "uppercase-species"))]
+pub type LaneMask = u128;
+
+const _: () = assert!(LANES <= LaneMask::BITS as usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StemVec(Lanes);
+
+#[cfg(all(
+    feature = "simd",
+    not(all(feature = "wide-alphabet", feature = "uppercase-species"))
+))]
+impl StemVec {
+    pub fn splat(value: StemCount) -> Self {
+        Self(Simd::splat(value))
+    }
+    pub fn from_array(array: [StemCount; LANES]) -> Self {
+        Self(Simd::from_array(array))
+    }
+    pub fn as_array(&self) -> &[StemCount; LANES] {
+        self.0.as_array()
+    }
+    pub fn as_mut_array(&mut self) -> &mut [StemCount; LANES] {
+        self.0.as_mut_array()
+    }
+    pub fn min(self, other: Self) -> Self {
+        Self(self.0.simd_min(other.0))
+    }
+    pub fn max(self, other: Self) -> Self {
+        Self(self.0.simd_max(other.0))
+    }
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+    pub fn total(self) -> StemTotal {
+        self.0.cast::<StemTotal>().reduce_sum()
+    }
+    pub fn reduce_min(self) -> StemCount {
+        self.0.reduce_min()
+    }
+    pub fn nonzero(self) -> LaneMask {
+        self.0.simd_ne(Simd::splat(0)).to_bitmask()
+    }
+    pub fn lanes_lt(self, other: Self) -> LaneMask {
+        self.0.simd_lt(other.0).to_bitmask()
+    }
+    pub fn lanes_gt(self, other: Self) -> LaneMask {
+        self.0.simd_gt(other.0).to_bitmask()
+    }
+    /// Takes the lanes of `if_true` where `mask` is set, and of `if_false` elsewhere.
+    pub fn select(mask: LaneMask, if_true: Self, if_false: Self) -> Self {
+        let mask = Mask::<<StemCount as SimdElement>::Mask, LANES>::from_bitmask(mask);
+        Self(mask.select(if_true.0, if_false.0))
+    }
+}
+
+#[cfg(not(all(
+    feature = "simd",
+    not(all(feature = "wide-alphabet", feature = "uppercase-species"))
+)))]
+impl StemVec {
+    pub fn splat(value: StemCount) -> Self {
+        Self([value; LANES])
+    }
+    pub fn from_array(array: [StemCount; LANES]) -> Self {
+        Self(array)
+    }
+    pub fn as_array(&self) -> &[StemCount; LANES] {
+        &self.0
+    }
+    pub fn as_mut_array(&mut self) -> &mut [StemCount; LANES] {
+        &mut self.0
+    }
+    fn zip(self, other: Self, f: impl Fn(StemCount, StemCount) -> StemCount) -> Self {
+        Self(std::array::from_fn(|lane| f(self.0[lane], other.0[lane])))
+    }
+    fn mask(self, other: Self, f: impl Fn(StemCount, StemCount) -> bool) -> LaneMask {
+        (0..LANES)
+            .filter(|lane| f(self.0[*lane], other.0[*lane]))
+            .fold(0, |mask, lane| mask | 1 << lane)
+    }
+    pub fn min(self, other: Self) -> Self {
+        self.zip(other, StemCount::min)
+    }
+    pub fn max(self, other: Self) -> Self {
+        self.zip(other, StemCount::max)
+    }
+    pub fn saturating_sub(self, other: Self) -> Self {
+        self.zip(other, StemCount::saturating_sub)
+    }
+    pub fn total(self) -> StemTotal {
+        self.0.iter().copied().map(StemTotal::from).sum()
+    }
+    pub fn reduce_min(self) -> StemCount {
+        self.0.iter().copied().min().unwrap_or(StemCount::MAX)
+    }
+    pub fn nonzero(self) -> LaneMask {
+        self.mask(Self::splat(0), |amount, _| amount != 0)
+    }
+    pub fn lanes_lt(self, other: Self) -> LaneMask {
+        self.mask(other, |a, b| a < b)
+    }
+    pub fn lanes_gt(self, other: Self) -> LaneMask {
+        self.mask(other, |a, b| a > b)
+    }
+    /// Takes the lanes of `if_true` where `mask` is set, and of `if_false` elsewhere.
+    pub fn select(mask: LaneMask, if_true: Self, if_false: Self) -> Self {
+        Self(std::array::from_fn(|lane| {
+            if mask & 1 << lane != 0 {
+                if_true.0[lane]
+            } else {
+                if_false.0[lane]
+            }
+        }))
+    }
+}
+
+impl Index<usize> for StemVec {
+    type Output = StemCount;
+    fn index(&self, lane: usize) -> &StemCount {
+        &self.as_array()[lane]
+    }
+}
+impl IndexMut<usize> for StemVec {
+    fn index_mut(&mut self, lane: usize) -> &mut StemCount {
+        &mut self.as_mut_array()[lane]
+    }
+}
+impl Index<Species> for StemVec {
+    type Output = StemCount;
+    fn index(&self, species: Species) -> &StemCount {
+        &self[species.index()]
+    }
+}
+impl IndexMut<Species> for StemVec {
+    fn index_mut(&mut self, species: Species) -> &mut StemCount {
+        &mut self[species.index()]
+    }
+}
+
+#[cfg(all(
+    feature = "simd",
+    not(all(feature = "wide-alphabet", feature = "uppercase-species"))
+))]
+macro_rules! lane_op {
+    ($trait:ident, $method:ident, $op:tt) => {
+        impl $trait for StemVec {
+            type Output = Self;
+            fn $method(self, other: Self) -> Self {
+                Self(self.0 $op other.0)
+            }
+        }
+    };
+}
+#[cfg(not(all(
+    feature = "simd",
+    not(all(feature = "wide-alphabet", feature = "uppercase-species"))
+)))]
+macro_rules! lane_op {
+    ($trait:ident, $method:ident, $op:tt) => {
+        impl $trait for StemVec {
+            type Output = Self;
+            fn $method(self, other: Self) -> Self {
+                self.zip(other, |a, b| a $op b)
+            }
+        }
+    };
+}
+lane_op!(Add, add, +);
+lane_op!(Sub, sub, -);
+lane_op!(Div, div, /);
+lane_op!(BitOr, bitor, |);
+
+/// Serialized as an array of a count per lane.
+#[cfg(feature = "serde")]
+impl serde::Serialize for StemVec {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.as_array())
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for StemVec {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let lanes = Vec::<StemCount>::deserialize(deserializer)?;
+        let lanes = <[StemCount; LANES]>::try_from(lanes).map_err(|lanes| {
+            serde::de::Error::invalid_length(lanes.len(), &format!("{LANES} lanes").as_str())
+        })?;
+        Ok(Self::from_array(lanes))
+    }
+}
+
+impl AddAssign for StemVec {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+impl SubAssign for StemVec {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
@@ -0,0 +1,1414 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Instant;
+use std::{io, io::BufRead, io::Write, thread};
+
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::bouquet::{Bouquet, Latency, OutputConfig, Writers};
+use crate::design::{Design, DesignParseError};
+use crate::observer::{EngineObserver, SharedObserver};
+use crate::production_line::{
+    Coverage, Duplicates, Hook, LineSnapshot, ProductionLine, RegisterError, Stats, StemError,
+    StemParseError, Strategy, TieBreak,
+};
+use crate::size::{Size, SizeMap, SIZES};
+use crate::species::{
+    char_to_stem_index, stem_total, stems_to_vec, vec_to_stems, Species, StemCount, StemTotal,
+    Stems, SPECIES,
+};
+
+#[derive(Debug)]
+pub enum InputError {
+    /// The design section is empty, e.g. because the input starts with a blank line.
+    EmptyInput,
+    InvalidHeader(String),
+    Design(DesignError),
+    Stem(StemError),
+    /// Reading the input or writing the bouquets failed, e.g. on input that is not
+    /// UTF-8.
+    Io(io::Error),
+}
+impl PartialEq for InputError {
+    /// I/O errors are equal if their kinds are, as `io::Error` can't be compared.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (InputError::EmptyInput, InputError::EmptyInput) => true,
+            (InputError::InvalidHeader(a), InputError::InvalidHeader(b)) => a == b,
+            (InputError::Design(a), InputError::Design(b)) => a == b,
+            (InputError::Stem(a), InputError::Stem(b)) => a == b,
+            (InputError::Io(a), InputError::Io(b)) => a.kind() == b.kind(),
+            _ => false,
+        }
+    }
+}
+impl From<io::Error> for InputError {
+    fn from(error: io::Error) -> Self {
+        InputError::Io(error)
+    }
+}
+
+/// Joins lines ending in a `\` with the lines after them, for design lines that are
+/// wrapped across lines. A line ending in an escaped `\\` is not continued.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineJoiner {
+    pending: Option<String>,
+}
+impl LineJoiner {
+    /// The whole line once `line` ends it, or `None` while it is continued.
+    pub fn push<'a>(&mut self, line: &'a str) -> Option<Cow<'a, str>> {
+        let continued = line
+            .strip_suffix('\\')
+            .filter(|start| !start.ends_with('\\'));
+        match (continued, &mut self.pending) {
+            (Some(start), Some(pending)) => {
+                pending.push_str(start);
+                None
+            }
+            (Some(start), None) => {
+                self.pending = Some(start.to_owned());
+                None
+            }
+            (None, pending) => match pending.take() {
+                Some(mut whole) => {
+                    whole.push_str(line);
+                    Some(Cow::Owned(whole))
+                }
+                None => Some(Cow::Borrowed(line)),
+            },
+        }
+    }
+    /// The continued line, if the last line pushed was continued.
+    pub fn finish(&mut self) -> Option<String> {
+        self.pending.take()
+    }
+}
+
+/// What `add_design` did with a valid design.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddOutcome {
+    Registered,
+    /// The design can never be made, as its species can't add up to its total, so it's
+    /// left out.
+    Skipped,
+    /// The design is already registered, and merged into it with `Duplicates::Merge`.
+    Duplicate,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DesignError {
+    Parse(DesignParseError),
+    UnknownSpecies(char),
+    Register(RegisterError),
+    /// The `#species=` header is not a count of species or a list of species, only
+    /// reported by `validate_designs`.
+    InvalidHeader,
+}
+
+/// A line in the stems section that makes every bouquet possible from the stock, e.g.
+/// at a shift boundary. Stems are accepted as usual afterwards.
+pub const DRAIN: &str = "DRAIN";
+
+/// Stock per size, leaving out species that are not in stock.
+pub type Stock = SizeMap<Vec<(char, StemCount)>>;
+
+type InvalidStemHandler = dyn FnMut(&str, &StemError);
+type InvalidDesignHandler = dyn FnMut(&str, &DesignError);
+
+/// The stock, designs and production of a warehouse, to resume processing after a
+/// restart with `Warehouse::restore`. Settings like the strategy are not included.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WarehouseState {
+    lines: SizeMap<LineSnapshot>,
+    design_count: usize,
+    /// Whether the designs have ended, so that input lines are stems.
+    in_stems: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Section {
+    Designs,
+    Stems,
+    End,
+}
+
+#[derive(Debug)]
+pub struct Warehouse<W: Write = io::Stdout> {
+    production_lines: SizeMap<ProductionLine>,
+    writers: Writers<W>,
+    output_config: OutputConfig,
+    species: [bool; SPECIES],
+    default_size: Option<Size>,
+    history: Option<Vec<Bouquet>>,
+    section: Section,
+    design_count: usize,
+    bouquet_count: u64,
+    on_batch_boundary: Hook<dyn FnMut(&Stock)>,
+    /// Whether stems were added since the last batch boundary.
+    in_batch: bool,
+    cascade: bool,
+    observer: Option<SharedObserver>,
+    on_invalid_stem: Hook<InvalidStemHandler>,
+    on_invalid_design: Hook<InvalidDesignHandler>,
+    separator: String,
+    on_skipped_design: Hook<dyn FnMut(&str)>,
+    on_unused_stem: Hook<dyn FnMut(char, Size)>,
+    /// The start of a design line continued on the next line.
+    continued: LineJoiner,
+    on_bouquet: Hook<dyn FnMut(&Bouquet)>,
+    parallel: bool,
+    /// The first failed write of a bouquet, returned by the next `process_line` or
+    /// `flush`.
+    write_error: Option<io::Error>,
+}
+
+impl Default for Warehouse {
+    fn default() -> Self {
+        Self::with_writer(io::stdout())
+    }
+}
+impl Warehouse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl Warehouse<io::Sink> {
+    /// Calls `on_bouquet` with the bouquets of all sizes, in the order they are made,
+    /// instead of writing them.
+    pub fn with_handler(on_bouquet: impl FnMut(&Bouquet) + 'static) -> Self {
+        let mut warehouse = Self::with_writer(io::sink());
+        warehouse.on_bouquet(on_bouquet);
+        warehouse
+    }
+}
+impl Warehouse<Box<dyn Write>> {
+    /// Writes the bouquets of every size to the writer of that size, like `with_writers`,
+    /// where each writer may be of another type, e.g. a file for the small bouquets and
+    /// stdout for the others.
+    pub fn with_separate_writers(
+        small: impl Write + 'static,
+        large: impl Write + 'static,
+        extra_large: impl Write + 'static,
+    ) -> Self {
+        let mut writers: [Option<Box<dyn Write>>; SIZES] = [
+            Some(Box::new(small)),
+            Some(Box::new(large)),
+            Some(Box::new(extra_large)),
+        ];
+        Self::with_writers(SizeMap::from_fn(|size| {
+            writers[size.to_index()].take().unwrap()
+        }))
+    }
+}
+impl<W: Write> Warehouse<W> {
+    /// Writes the bouquets of all sizes to `writer`, in the order they are made.
+    pub fn with_writer(writer: W) -> Self {
+        Self::with_output(Writers::Shared(writer))
+    }
+    /// Writes the bouquets of every size to the writer of that size.
+    pub fn with_writers(writers: SizeMap<W>) -> Self {
+        Self::with_output(Writers::PerSize(writers))
+    }
+    pub(crate) fn with_output(writers: Writers<W>) -> Self {
+        Self {
+            production_lines: SizeMap::default(),
+            writers,
+            output_config: OutputConfig::default(),
+            species: [true; SPECIES],
+            default_size: None,
+            history: None,
+            section: Section::Designs,
+            design_count: 0,
+            bouquet_count: 0,
+            on_batch_boundary: Hook::default(),
+            in_batch: false,
+            cascade: false,
+            observer: None,
+            on_invalid_stem: Hook::default(),
+            on_invalid_design: Hook::default(),
+            separator: String::new(),
+            on_skipped_design: Hook::default(),
+            on_unused_stem: Hook::default(),
+            continued: LineJoiner::default(),
+            on_bouquet: Hook::default(),
+            parallel: false,
+            write_error: None,
+        }
+    }
+    pub fn into_writers(self) -> Writers<W> {
+        self.writers
+    }
+    /// Restricts the accepted species to the given alphabet, either a list of species
+    /// like `abcdef` or a count like `6` for the first species of `a`-`z`.
+    ///
+    /// Species keep their lane in the stem vectors; designs and stems using a species
+    /// outside of the alphabet are rejected.
+    pub fn set_alphabet(&mut self, alphabet: &str) -> Result<(), InputError> {
+        self.species = parse_alphabet(alphabet)?;
+        Ok(())
+    }
+    pub fn snapshot(&self) -> WarehouseState {
+        WarehouseState {
+            lines: SizeMap::from_fn(|size| self.production_lines.get(size).snapshot()),
+            design_count: self.design_count,
+            in_stems: self.section != Section::Designs,
+        }
+    }
+    /// Continues from `state`, taken with `snapshot`, as if there was no restart. The
+    /// settings of this warehouse are kept, and should match the ones of the snapshot.
+    /// Fails without changing the warehouse if its settings refuse a design of the
+    /// snapshot, e.g. a duplicate with `Duplicates::Reject`.
+    pub fn restore(&mut self, state: WarehouseState) -> Result<(), RegisterError> {
+        let mut production_lines = self.production_lines.clone();
+        for (size, production_line) in production_lines.iter_mut() {
+            production_line.restore(&state.lines[size])?;
+        }
+        self.production_lines = production_lines;
+        self.design_count = state.design_count;
+        self.section = if state.in_stems {
+            Section::Stems
+        } else {
+            Section::Designs
+        };
+        Ok(())
+    }
+    /// Approximate bytes used by the warehouse, not counting the writers' buffers.
+    pub fn memory_footprint(&self) -> usize {
+        let history = self
+            .history
+            .as_ref()
+            .map_or(0, |history| history.capacity() * size_of::<Bouquet>());
+        size_of::<Self>() - SIZES * size_of::<ProductionLine>()
+            + self
+                .production_lines
+                .values()
+                .map(ProductionLine::memory_footprint)
+                .sum::<usize>()
+            + history
+    }
+    /// Keeps every produced bouquet in memory, which is needed for `write_csv`.
+    pub fn set_keep_history(&mut self, keep_history: bool) {
+        self.history = keep_history.then(Vec::new);
+    }
+    /// Writes the produced bouquets as CSV, with a column for each species used by the
+    /// catalog. Requires `set_keep_history` to be enabled before producing.
+    pub fn write_csv<O: Write>(&self, mut out: O) -> io::Result<()> {
+        let used = self
+            .production_lines
+            .values()
+            .flat_map(|production_line| production_line.designs.iter())
+            .fold(Stems::splat(0), |used, design| used | design.max_stems);
+        let species: Vec<Species> = Species::all()
+            .filter(|species| used[*species] != 0)
+            .collect();
+        write!(out, "name,size,total")?;
+        for species in &species {
+            write!(out, ",{species}")?;
+        }
+        writeln!(out)?;
+        for bouquet in self.history.iter().flatten() {
+            write!(
+                out,
+                "{},{},{}",
+                csv_field(&bouquet.name.to_string()),
+                csv_field(&bouquet.size.to_string()),
+                stem_total(bouquet.stems)
+            )?;
+            for species in &species {
+                write!(out, ",{}", bouquet.stems[*species])?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+    /// Routes designs and stems without a size to `default_size`.
+    pub fn set_default_size(&mut self, default_size: Option<Size>) {
+        self.default_size = default_size;
+    }
+    pub fn add_design(&mut self, design_str: &str) -> Result<AddOutcome, DesignError> {
+        let outcome = self.register_design(design_str);
+        self.observe_design(design_str, &outcome);
+        outcome
+    }
+    /// Reports a design line that was not registered as a design of its own.
+    fn observe_design(&self, design_str: &str, outcome: &Result<AddOutcome, DesignError>) {
+        let Some(observer) = &self.observer else {
+            return;
+        };
+        match outcome {
+            Ok(AddOutcome::Registered) => {}
+            Ok(AddOutcome::Duplicate) => observer.0.borrow_mut().on_merge(design_str),
+            Ok(AddOutcome::Skipped) | Err(_) => observer.0.borrow_mut().on_reject(design_str),
+        }
+    }
+    fn register_design(&mut self, design_str: &str) -> Result<AddOutcome, DesignError> {
+        let design = parse_design(design_str, self.default_size, &self.species)?;
+        self.insert_design(design)
+    }
+    fn insert_design(&mut self, design: Design) -> Result<AddOutcome, DesignError> {
+        // `Design::new` rejects a total below the number of species, but not one above
+        // what the maximums of the species add up to.
+        if design.total > stem_total(design.max_stems)
+            || design.total < stem_total(design.min_stems)
+        {
+            return Ok(AddOutcome::Skipped);
+        }
+        let production_line = self.production_lines.get_mut(design.size);
+        let registered = production_line.designs.len();
+        let design_id = production_line
+            .add_design(design)
+            .map_err(DesignError::Register)?;
+        // A merged duplicate is one of the designs registered before.
+        if design_id.index() < registered {
+            return Ok(AddOutcome::Duplicate);
+        }
+        Ok(AddOutcome::Registered)
+    }
+    /// Adds every design line and preprocesses, so the stems can follow. Stops at the
+    /// first invalid line, leaving the designs after it out, unless `collect_errors` is
+    /// set, which skips every invalid line instead. The errors come with the position of
+    /// their line in `lines`, counting from 0.
+    pub fn add_designs<'a>(
+        &mut self,
+        lines: impl IntoIterator<Item = &'a str>,
+        collect_errors: bool,
+    ) -> Result<(), Vec<(usize, DesignError)>> {
+        let errors = collect(lines, collect_errors, |line| {
+            self.add_design(line).map(drop)
+        });
+        self.preprocess();
+        errors
+    }
+    /// Adds every design line like `add_designs`, parsing the lines in parallel. The
+    /// parsed designs are registered in the order of `lines`, so the catalog is the same
+    /// as with `add_designs`.
+    #[cfg(feature = "rayon")]
+    pub fn add_designs_par<I>(
+        &mut self,
+        lines: I,
+        collect_errors: bool,
+    ) -> Result<(), Vec<(usize, DesignError)>>
+    where
+        I: IntoParallelIterator<Item = String>,
+    {
+        let (default_size, alphabet) = (self.default_size, self.species);
+        let parsed: Vec<(String, Result<Design, DesignError>)> = lines
+            .into_par_iter()
+            .map(|line| {
+                let design = parse_design(&line, default_size, &alphabet);
+                (line, design)
+            })
+            .collect();
+        let mut errors = Vec::new();
+        for (position, (line, design)) in parsed.into_iter().enumerate() {
+            let outcome = design.and_then(|design| self.insert_design(design));
+            self.observe_design(&line, &outcome);
+            if let Err(error) = outcome {
+                errors.push((position, error));
+                if !collect_errors {
+                    break;
+                }
+            }
+        }
+        self.preprocess();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+    pub fn preprocess(&mut self) {
+        for production_line in self.production_lines.values_mut() {
+            production_line.preprocess();
+        }
+    }
+    /// Preprocesses like `preprocess`, and reports how the designs of every size cover
+    /// the species.
+    pub fn preprocess_with_report(&mut self) -> SizeMap<Coverage> {
+        SizeMap::from_fn(|size| self.production_lines.get_mut(size).preprocess_with_report())
+    }
+    /// Adds every stem line, like `add_designs` does for designs. With `set_parallel`,
+    /// an error only stops the stems of its own size, and without `collect_errors` only
+    /// the first error is returned.
+    pub fn add_stems<'a>(
+        &mut self,
+        lines: impl IntoIterator<Item = &'a str>,
+        collect_errors: bool,
+    ) -> Result<(), Vec<(usize, StemError)>> {
+        if self.parallel {
+            return self.add_stems_parallel(lines, collect_errors);
+        }
+        collect(lines, collect_errors, |line| self.add_stem(line))
+    }
+    pub fn best_completion(
+        &self,
+        size: Size,
+        partial: &[(char, StemCount)],
+    ) -> Result<Option<char>, StemParseError> {
+        Ok(self
+            .production_lines
+            .get(size)
+            .best_completion(vec_to_stems(partial)?))
+    }
+    /// Holds back design `after` of `size` until at least one `before` has been made.
+    pub fn add_precedence(&mut self, size: Size, before: char, after: char) {
+        self.production_lines
+            .get_mut(size)
+            .add_precedence(before, after);
+    }
+    /// Runs `stems` on a copy of the catalog where `design` is always preferred, leaving
+    /// this warehouse untouched.
+    pub fn simulate_priority(&self, design: char, stems: &[(char, Size)]) -> Stats {
+        let mut production_lines = self.production_lines.clone();
+        for production_line in production_lines.values_mut() {
+            production_line.prioritize(design);
+        }
+        simulate(&mut production_lines, stems)
+    }
+    pub fn conflict(
+        &self,
+        size: Size,
+        a: char,
+        b: char,
+        stock: &[(char, StemCount)],
+    ) -> Result<bool, StemParseError> {
+        Ok(self
+            .production_lines
+            .get(size)
+            .conflict(a, b, vec_to_stems(stock)?))
+    }
+    pub fn set_price(&mut self, size: Size, name: char, price: u32) {
+        if let Some(design) = self.production_lines.get_mut(size).design_mut(name) {
+            design.price = price;
+        }
+    }
+    pub fn set_max_age(&mut self, size: Size, name: char, max_age: Option<u64>) {
+        self.production_lines
+            .get_mut(size)
+            .set_max_age(name, max_age);
+    }
+    /// Limits the stems in stock of `size` to `capacity` in total, see
+    /// `ProductionLine::set_capacity`.
+    pub fn set_capacity(&mut self, size: Size, capacity: Option<StemTotal>) {
+        self.production_lines.get_mut(size).set_capacity(capacity);
+    }
+    /// The amount of stems of `size` in stock, like `ProductionLine::stock_total`.
+    pub fn stock_total(&self, size: Size) -> u32 {
+        self.production_lines.get(size).stock_total()
+    }
+    /// The stock of `size` as a percentage of its capacity, if it has one.
+    pub fn utilization(&self, size: Size) -> Option<f32> {
+        self.production_lines.get(size).utilization()
+    }
+    pub fn set_quota(&mut self, size: Size, name: char, quota: Option<u32>) {
+        self.production_lines.get_mut(size).set_quota(name, quota);
+    }
+    pub fn set_group(&mut self, size: Size, name: char, group: &str) {
+        if let Some(design) = self.production_lines.get_mut(size).design_mut(name) {
+            design.group = Some(group.to_owned());
+        }
+    }
+    /// Only makes designs in one of `groups`, besides the designs without a group.
+    pub fn set_active_groups(&mut self, groups: &[&str]) {
+        let groups: Vec<String> = groups.iter().map(|group| group.to_string()).collect();
+        for production_line in self.production_lines.values_mut() {
+            production_line.active_groups = Some(groups.clone());
+        }
+    }
+    pub fn total_revenue(&self) -> u64 {
+        self.production_lines
+            .values()
+            .map(ProductionLine::revenue)
+            .sum()
+    }
+    /// The designs of `size` that were registered, after the optimization passes.
+    pub fn designs(&self, size: Size) -> impl Iterator<Item = &Design> {
+        self.production_lines.get(size).designs()
+    }
+    pub fn nearest_design(&self, size: Size, bouquet: &Bouquet) -> Option<(char, u32)> {
+        self.production_lines.get(size).nearest_design(bouquet)
+    }
+    /// Species used by at least `min_designs` designs of `size`.
+    pub fn shared_species(&self, size: Size, min_designs: usize) -> Vec<char> {
+        let production_line = self.production_lines.get(size);
+        Species::all()
+            .filter(|species| {
+                let design_count = production_line.design_count(*species);
+                design_count != 0 && design_count >= min_designs
+            })
+            .map(Species::to_char)
+            .collect()
+    }
+    /// The design that can use the most stems of `species`.
+    pub fn top_consumer(&self, size: Size, species: char) -> Option<char> {
+        self.production_lines
+            .get(size)
+            .top_consumer(Species::from_char(species)?)
+    }
+    /// The design of `size` that the next stem of `species` would make a bouquet of.
+    pub fn next_design_for(&self, size: Size, species: char) -> Option<char> {
+        self.production_lines
+            .get(size)
+            .next_design_for(Species::from_char(species)?)
+    }
+    /// Designs that can be made from the current stock, the design of which the most
+    /// bouquets could be made first. Like the stems, this leaves out designs past their
+    /// quota, precedence or group, and stems past their maximum age.
+    pub fn producible_now(&self, size: Size) -> Vec<char> {
+        self.production_lines.get(size).producible_now()
+    }
+    /// Stems needed to produce every design of `size` once.
+    ///
+    /// Designs can't share stems, so this is the sum of one bouquet per design: its
+    /// per-species minimums, topped up to its total with the species of the design in
+    /// alphabetical order, up to their maximums. The amounts saturate at `u32::MAX`.
+    pub fn min_stock_for_full_catalog(&self, size: Size) -> Vec<(char, u32)> {
+        self.production_lines
+            .get(size)
+            .min_stock_for_full_catalog()
+            .iter()
+            .enumerate()
+            .filter(|(_, amount)| **amount != 0)
+            .map(|(stem_index, amount)| (Species::from_index(stem_index).to_char(), *amount))
+            .collect()
+    }
+    /// The worst-case demand per species of a single round, if every design of `size` is
+    /// made with its maximum stems. Species without demand are left out.
+    pub fn total_demand(&self, size: Size) -> Vec<(char, u32)> {
+        self.production_lines
+            .get(size)
+            .total_demand()
+            .iter()
+            .enumerate()
+            .filter(|(_, demand)| **demand != 0)
+            .map(|(stem_index, demand)| (Species::from_index(stem_index).to_char(), *demand))
+            .collect()
+    }
+    /// Bouquets made per design for every size, for the whole stem stream so far.
+    pub fn stats(&self) -> SizeMap<Vec<(char, u32)>> {
+        SizeMap::from_fn(|size| self.production_lines.get(size).production())
+    }
+    /// Stems received of every size, including the stems that were never used.
+    pub fn stems_received(&self) -> u64 {
+        self.production_lines
+            .values()
+            .flat_map(|production_line| production_line.arrivals())
+            .map(|arrivals| u64::from(*arrivals))
+            .sum()
+    }
+    /// Stems consumed per design for every size, for the whole stem stream so far.
+    pub fn consumed_stems(&self) -> SizeMap<Vec<(char, u32)>> {
+        SizeMap::from_fn(|size| self.production_lines.get(size).consumption())
+    }
+    /// The designs of every size that need a species of which no stem arrived so far,
+    /// with those species. These designs were never made for lack of supply.
+    pub fn starved_designs(&self) -> SizeMap<Vec<(char, Vec<char>)>> {
+        SizeMap::from_fn(|size| self.production_lines.get(size).starved_designs())
+    }
+    /// Suggests a subset of the designs of `size` for an expected share of arrivals per
+    /// species.
+    ///
+    /// Designs that need a species without supply are never suggested. Of the others,
+    /// the design that uses the most uncovered supply is picked first, preferring the
+    /// simplest design, until every supplied species is used by a picked design.
+    #[cfg(feature = "recommend")]
+    pub fn recommend_subset(
+        &self,
+        size: Size,
+        distribution: &[(char, f64)],
+    ) -> Result<Vec<char>, StemParseError> {
+        let mut uncovered = [0.0; SPECIES];
+        for (species, share) in distribution {
+            uncovered[Species::try_from(*species)?.index()] += share.max(0.0);
+        }
+        let mut candidates: Vec<&Design> = self
+            .production_lines
+            .get(size)
+            .designs
+            .iter()
+            .filter(|design| {
+                (0..SPECIES).all(|stem_index| {
+                    design.min_stems[stem_index] == 0 || uncovered[stem_index] > 0.0
+                })
+            })
+            .collect();
+        candidates.sort_by_key(|design| design.total);
+        let mut subset = Vec::new();
+        loop {
+            let coverage = |design: &Design| -> f64 {
+                (0..SPECIES)
+                    .filter(|stem_index| design.max_stems[*stem_index] != 0)
+                    .map(|stem_index| uncovered[stem_index])
+                    .sum()
+            };
+            let best = candidates
+                .iter()
+                .enumerate()
+                .map(|(position, design)| (position, coverage(design)))
+                .filter(|(_, coverage)| *coverage > 0.0)
+                .fold(None, |best: Option<(usize, f64)>, (position, coverage)| {
+                    // Only a strictly larger coverage wins, so ties keep the simplest.
+                    match best {
+                        Some((_, best_coverage)) if best_coverage >= coverage => best,
+                        _ => Some((position, coverage)),
+                    }
+                });
+            let Some((position, _)) = best else {
+                break;
+            };
+            let design = candidates.remove(position);
+            for (stem_index, uncovered) in uncovered.iter_mut().enumerate() {
+                if design.max_stems[stem_index] != 0 {
+                    *uncovered = 0.0;
+                }
+            }
+            subset.push(design.name);
+        }
+        Ok(subset)
+    }
+    /// Returns `(dominated, dominator)` pairs of designs that use the same species,
+    /// where the dominated design has no higher maximum for any species and no higher
+    /// total.
+    pub fn dominated_designs(&self, size: Size) -> Vec<(char, char)> {
+        self.production_lines.get(size).dominated_designs()
+    }
+    pub fn set_output_config(&mut self, output_config: OutputConfig) {
+        self.output_config = output_config;
+    }
+    /// Makes every bouquet possible after a stem, instead of at most one per stem.
+    pub fn set_cascade(&mut self, cascade: bool) {
+        self.cascade = cascade;
+    }
+    pub fn set_strategy(&mut self, strategy: Strategy) {
+        for production_line in self.production_lines.values_mut() {
+            production_line.set_strategy(strategy);
+        }
+    }
+    pub fn set_tie_break(&mut self, tie_break: TieBreak) {
+        for production_line in self.production_lines.values_mut() {
+            production_line.set_tie_break(tie_break);
+        }
+    }
+    /// Sets the species that excess stems are returned from first, like
+    /// `ProductionLine::set_trim_order`. Values that are not species are left out.
+    pub fn set_trim_order(&mut self, trim_order: &[char]) {
+        let trim_order: Vec<Species> = trim_order
+            .iter()
+            .filter_map(|species| Species::from_char(*species))
+            .collect();
+        for production_line in self.production_lines.values_mut() {
+            production_line.set_trim_order(trim_order.clone());
+        }
+    }
+    pub fn set_duplicates(&mut self, duplicates: Duplicates) {
+        for production_line in self.production_lines.values_mut() {
+            production_line.set_duplicates(duplicates);
+        }
+    }
+    /// Sends the events of this warehouse to `observer`.
+    pub fn set_observer(&mut self, observer: impl EngineObserver + 'static) {
+        for production_line in self.production_lines.values_mut() {
+            production_line.depleted = Some(Vec::new());
+        }
+        self.observer = Some(SharedObserver(Rc::new(RefCell::new(observer))));
+    }
+    /// Runs the stems of every size on a thread of their own in `add_stems`. The
+    /// bouquets of a size are written in the order they are made, but all bouquets of
+    /// one size are written before those of the next, in the order of `Size::ALL`.
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
+    pub fn set_strict(&mut self, strict: bool) {
+        for production_line in self.production_lines.values_mut() {
+            production_line.strict = strict;
+        }
+    }
+    /// Sets the line that separates the designs from the stems and ends the stems,
+    /// instead of a blank line. Blank lines are then ignored.
+    pub fn set_separator(&mut self, separator: &str) {
+        self.separator = separator.to_owned();
+    }
+    /// Processes one line of input: the optional `#species=` header, the designs, the
+    /// separator, the stems, and the separator to end the input, unless the separator is
+    /// the blank line, which is ignored among the stems. Surrounding whitespace,
+    /// like the `\r` of a CRLF file, is ignored, so a line of only whitespace is blank.
+    pub fn process_line(&mut self, line: &str) -> Result<(), InputError> {
+        let line = line.trim();
+        match self.section {
+            _ if line.is_empty() && !self.separator.is_empty() => {}
+            Section::Designs if line == self.separator => self.end_designs()?,
+            Section::Designs => match line.strip_prefix("#species=") {
+                Some(alphabet) if self.design_count == 0 => self.set_alphabet(alphabet)?,
+                _ => {
+                    if let Some(line) = self.continued.push(line) {
+                        self.process_design(&line)?;
+                    }
+                }
+            },
+            Section::Stems if line == DRAIN => self.drain(),
+            Section::Stems if line == self.separator => match self.on_batch_boundary.0 {
+                Some(_) => self.end_batch(),
+                // A blank line among the stems is likely a stray one, so only an explicit
+                // separator ends the input before the end of the reader.
+                None if line.is_empty() => {}
+                None => self.section = Section::End,
+            },
+            Section::Stems => {
+                match self.add_stem(line) {
+                    Ok(()) => {}
+                    Err(error) => match &mut self.on_invalid_stem.0 {
+                        Some(on_invalid_stem) => on_invalid_stem(line, &error),
+                        None => return Err(InputError::Stem(error)),
+                    },
+                }
+                self.in_batch = true;
+            }
+            Section::End => {}
+        }
+        match self.write_error.take() {
+            Some(error) => Err(InputError::Io(error)),
+            None => Ok(()),
+        }
+    }
+    /// Adds a whole design line, after joining its continued lines.
+    fn process_design(&mut self, line: &str) -> Result<(), InputError> {
+        match self.add_design(line) {
+            Ok(AddOutcome::Registered | AddOutcome::Duplicate) => {}
+            Ok(AddOutcome::Skipped) => {
+                if let Some(on_skipped_design) = &mut self.on_skipped_design.0 {
+                    on_skipped_design(line);
+                }
+            }
+            Err(error) => match &mut self.on_invalid_design.0 {
+                Some(on_invalid_design) => on_invalid_design(line, &error),
+                None => return Err(InputError::Design(error)),
+            },
+        }
+        self.design_count += 1;
+        Ok(())
+    }
+    /// Ends the designs, like the separator line does.
+    pub fn end_designs(&mut self) -> Result<(), InputError> {
+        // A design continued on the last line before the separator ends there.
+        if let Some(line) = self.continued.finish() {
+            self.process_design(&line)?;
+        }
+        if self.design_count == 0 {
+            return Err(InputError::EmptyInput);
+        }
+        self.preprocess();
+        self.section = Section::Stems;
+        Ok(())
+    }
+    /// Processes the designs up to the separator or the end of the reader, leaving the
+    /// rest of the reader for the stems.
+    pub fn process_designs(&mut self, mut reader: impl BufRead) -> Result<(), InputError> {
+        let mut line = String::new();
+        while read_line(&mut reader, &mut line)? {
+            self.process_line(&line)?;
+            if self.section != Section::Designs {
+                return Ok(());
+            }
+        }
+        self.end_designs()
+    }
+    /// Processes the stems up to the end of the reader, ignoring blank lines, e.g. for a
+    /// file with only stems.
+    pub fn process_stems(&mut self, mut reader: impl BufRead) -> Result<(), InputError> {
+        let mut line = String::new();
+        while read_line(&mut reader, &mut line)? {
+            if !line.trim().is_empty() {
+                self.process_line(&line)?;
+            }
+        }
+        self.finish()
+    }
+    pub fn process(&mut self, mut reader: impl BufRead) -> Result<(), InputError> {
+        let mut line = String::new();
+        while read_line(&mut reader, &mut line)? {
+            self.process_line(&line)?;
+            if self.section == Section::End {
+                break;
+            }
+        }
+        self.finish()
+    }
+    /// Like `process`, but reads on a separate thread so a heartbeat can be written
+    /// while waiting for input, if `OutputConfig::heartbeat` is set.
+    pub fn process_streaming<R: BufRead + Send + 'static>(
+        &mut self,
+        reader: R,
+    ) -> Result<(), InputError> {
+        let heartbeat = match self.output_config.heartbeat {
+            Some(heartbeat) => heartbeat,
+            None => return self.process(reader),
+        };
+        let (sender, receiver) = mpsc::channel();
+        // The reader thread is not joined, as it may be blocked on input we don't
+        // need anymore. It stops at the end of the input, at a read error or once the
+        // receiver is gone.
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let failed = line.is_err();
+                if sender.send(line).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let mut last_output = Instant::now();
+        while self.section != Section::End {
+            match receiver.recv_timeout(heartbeat.saturating_sub(last_output.elapsed())) {
+                Ok(line) => {
+                    let line = line?;
+                    let bouquet_count = self.bouquet_count;
+                    self.process_line(&line)?;
+                    if self.bouquet_count != bouquet_count {
+                        last_output = Instant::now();
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    self.writers.try_for_each(|writer| {
+                        writeln!(writer, "# heartbeat")?;
+                        writer.flush()
+                    })?;
+                    last_output = Instant::now();
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        self.finish()
+    }
+    /// Processes the stem section in batches separated by blank lines, calling
+    /// `on_batch_boundary` with the remaining stock after every batch. The input then
+    /// ends at the end of the reader instead of at a blank line.
+    pub fn on_batch_boundary(&mut self, on_batch_boundary: impl FnMut(&Stock) + 'static) {
+        self.on_batch_boundary = Hook(Some(Box::new(on_batch_boundary)));
+    }
+    /// Skips stem lines that can't be added after calling `on_invalid_stem` with the
+    /// line and the error, instead of failing with `InputError::Stem`.
+    pub fn on_invalid_stem(&mut self, on_invalid_stem: impl FnMut(&str, &StemError) + 'static) {
+        self.on_invalid_stem = Hook(Some(Box::new(on_invalid_stem)));
+    }
+    /// Skips design lines that can't be added after calling `on_invalid_design` with
+    /// the line and the error, instead of failing with `InputError::Design`.
+    pub fn on_invalid_design(
+        &mut self,
+        on_invalid_design: impl FnMut(&str, &DesignError) + 'static,
+    ) {
+        self.on_invalid_design = Hook(Some(Box::new(on_invalid_design)));
+    }
+    /// Calls `on_skipped_design` with every design line that is skipped because it can
+    /// never be made.
+    pub fn on_skipped_design(&mut self, on_skipped_design: impl FnMut(&str) + 'static) {
+        self.on_skipped_design = Hook(Some(Box::new(on_skipped_design)));
+    }
+    /// Calls `on_unused_stem` with the species and size of every stem line added to a
+    /// line without designs using the species. Such stems stay in stock for good.
+    pub fn on_unused_stem(&mut self, on_unused_stem: impl FnMut(char, Size) + 'static) {
+        self.on_unused_stem = Hook(Some(Box::new(on_unused_stem)));
+    }
+    /// Reports the stems that no design of their size uses to `on_unused_stem`.
+    fn check_used(&mut self, species: Species, size: Size) {
+        if let Some(on_unused_stem) = &mut self.on_unused_stem.0 {
+            if self.production_lines.get(size).design_count(species) == 0 {
+                on_unused_stem(species.to_char(), size);
+            }
+        }
+    }
+    /// Calls `on_bouquet` with every bouquet that is made, instead of writing it to the
+    /// writers.
+    pub fn on_bouquet(&mut self, on_bouquet: impl FnMut(&Bouquet) + 'static) {
+        self.on_bouquet = Hook(Some(Box::new(on_bouquet)));
+    }
+    fn end_batch(&mut self) {
+        if !self.in_batch {
+            return;
+        }
+        self.in_batch = false;
+        let stock = self.remaining_stems();
+        if let Some(on_batch_boundary) = &mut self.on_batch_boundary.0 {
+            on_batch_boundary(&stock);
+        }
+    }
+    /// The stems of `species` and `size` in stock, which is 0 if `species` is not a
+    /// species.
+    pub fn stem_count(&self, species: char, size: Size) -> StemCount {
+        Species::from_char(species)
+            .map_or(0, |species| self.production_lines.get(size).stems[species])
+    }
+    /// The stems of `size` in stock per species, leaving out the species without stems.
+    pub fn inventory(&self, size: Size) -> Vec<(char, StemCount)> {
+        stems_to_vec(self.production_lines.get(size).stems)
+    }
+    /// The stems left in stock per size, e.g. after the stem stream ended.
+    pub fn remaining_stems(&self) -> Stock {
+        SizeMap::from_fn(|size| stems_to_vec(self.production_lines.get(size).stems))
+    }
+    /// The stems left in stock per size like `remaining_stems`, the species with the
+    /// most stems first and species with the same amount in alphabetical order.
+    pub fn leftovers_report(&self) -> Stock {
+        let mut leftovers = self.remaining_stems();
+        for stems in leftovers.values_mut() {
+            stems.sort_by_key(|(species, amount)| {
+                (std::cmp::Reverse(*amount), char_to_stem_index(*species))
+            });
+        }
+        leftovers
+    }
+    fn finish(&mut self) -> Result<(), InputError> {
+        if let Some(line) = self.continued.finish() {
+            self.process_design(&line)?;
+        }
+        if self.section == Section::Designs && self.design_count == 0 {
+            return Err(InputError::EmptyInput);
+        }
+        self.end_batch();
+        self.flush()?;
+        Ok(())
+    }
+    /// Flushes the writers, or returns the first failed write of a bouquet since the
+    /// last flush.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(error) = self.write_error.take() {
+            return Err(error);
+        }
+        self.writers.try_for_each(|writer| writer.flush())
+    }
+    /// Checks every design line of `reader` up to the separator without registering
+    /// them, returning all invalid lines with their errors, e.g. to stop before reading
+    /// any stem. A valid `#species=` header before the first design line applies to the
+    /// lines after it, like in `process_line`, and an invalid one is reported as
+    /// `DesignError::InvalidHeader`.
+    pub fn validate_designs<R: BufRead>(
+        &self,
+        reader: R,
+    ) -> io::Result<Vec<(String, DesignError)>> {
+        let mut alphabet = self.species;
+        let mut errors = Vec::new();
+        let mut continued = LineJoiner::default();
+        let mut design_count = 0;
+        let validate = |line: &str, alphabet: &[bool; SPECIES]| {
+            parse_design(line, self.default_size, alphabet)
+                .err()
+                .map(|error| (line.to_owned(), error))
+        };
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line == self.separator {
+                break;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix("#species=") {
+                if design_count == 0 {
+                    match parse_alphabet(header) {
+                        Ok(header) => alphabet = header,
+                        Err(_) => errors.push((line.to_owned(), DesignError::InvalidHeader)),
+                    }
+                    continue;
+                }
+            }
+            if let Some(line) = continued.push(line) {
+                errors.extend(validate(&line, &alphabet));
+                design_count += 1;
+            }
+        }
+        if let Some(line) = continued.finish() {
+            errors.extend(validate(&line, &alphabet));
+        }
+        Ok(errors)
+    }
+    /// Checks every stem line of `reader` without processing them, returning the
+    /// parse errors with their (1-based) line numbers. Blank lines are ignored.
+    pub fn validate_stems<R: BufRead>(
+        &self,
+        reader: R,
+    ) -> io::Result<Vec<(usize, StemParseError)>> {
+        let mut errors = Vec::new();
+        for (line_index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Err(error) = self.parse_stem(line.trim()) {
+                errors.push((line_index + 1, error));
+            }
+        }
+        Ok(errors)
+    }
+    /// Parses a stem line with an optional leading count, e.g. `5aL` for five `aL` stems.
+    fn parse_stem(&self, stem_str: &str) -> Result<(u32, Species, Size), StemParseError> {
+        let digits = stem_str.len()
+            - stem_str
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let (count, stem_str) = stem_str.split_at(digits);
+        let count = match count {
+            "" => 1,
+            count => match count.parse() {
+                Ok(0) | Err(_) => return Err(StemParseError::InvalidCount(count.to_string())),
+                Ok(count) => count,
+            },
+        };
+        let mut chars = stem_str.chars();
+        let value = chars.next().ok_or(StemParseError::Empty)?;
+        let species = Species::try_from(value)?;
+        if !self.species[species.index()] {
+            return Err(StemParseError::UnknownSpecies(value));
+        }
+        let size = match (chars.as_str(), &self.default_size) {
+            ("", Some(size)) => *size,
+            (size, _) => Size::from_str(size).map_err(StemParseError::InvalidSize)?,
+        };
+        Ok((count, species, size))
+    }
+    /// Adds a stem line, which may start with a count of stems of the same species.
+    pub fn add_stem(&mut self, stem_str: &str) -> Result<(), StemError> {
+        let (count, species, size) = match self.parse_stem(stem_str) {
+            Ok(stem) => stem,
+            Err(error) => {
+                if let Some(observer) = &self.observer {
+                    observer.0.borrow_mut().on_reject(stem_str);
+                }
+                return Err(StemError::Parse(error));
+            }
+        };
+        self.check_used(species, size);
+        for _ in 0..count {
+            if let Some(observer) = &self.observer {
+                observer.0.borrow_mut().on_stem(species.to_char(), size);
+            }
+            let production_line = self.production_lines.get_mut(size);
+            let bouquets = add_to_line(production_line, species, self.cascade)?;
+            self.emit_all(size, bouquets);
+        }
+        Ok(())
+    }
+    /// Takes the stems of a stem line back out of stock, the inverse of `add_stem`.
+    /// Removing more stems than are in stock leaves none.
+    pub fn remove_stem(&mut self, stem_str: &str) -> Result<(), StemError> {
+        let (count, species, size) = self.parse_stem(stem_str).map_err(StemError::Parse)?;
+        let production_line = self.production_lines.get_mut(size);
+        for _ in 0..count {
+            if !production_line.remove_stem(species) {
+                break;
+            }
+        }
+        Ok(())
+    }
+    fn add_stems_parallel<'a>(
+        &mut self,
+        lines: impl IntoIterator<Item = &'a str>,
+        collect_errors: bool,
+    ) -> Result<(), Vec<(usize, StemError)>> {
+        let mut errors = Vec::new();
+        let mut stems: SizeMap<Vec<(usize, Species)>> = SizeMap::default();
+        for (position, line) in lines.into_iter().enumerate() {
+            match self.parse_stem(line) {
+                Ok((count, species, size)) => {
+                    self.check_used(species, size);
+                    stems
+                        .get_mut(size)
+                        .extend(std::iter::repeat_n((position, species), count as usize));
+                }
+                Err(error) => {
+                    if let Some(observer) = &self.observer {
+                        observer.0.borrow_mut().on_reject(line);
+                    }
+                    errors.push((position, StemError::Parse(error)));
+                    if !collect_errors {
+                        break;
+                    }
+                }
+            }
+        }
+        let cascade = self.cascade;
+        let added: Vec<Vec<Added>> = thread::scope(|scope| {
+            let threads: Vec<_> = self
+                .production_lines
+                .values_mut()
+                .zip(stems.values())
+                .map(|(production_line, stems)| {
+                    scope.spawn(move || {
+                        let mut added = Vec::with_capacity(stems.len());
+                        for (position, species) in stems {
+                            let result = add_to_line(production_line, *species, cascade)
+                                .map(|bouquets| (production_line.take_depleted(), bouquets));
+                            let failed = result.is_err();
+                            added.push((*position, *species, result));
+                            if failed && !collect_errors {
+                                break;
+                            }
+                        }
+                        added
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .collect()
+        });
+        // The events are replayed on this thread, as the observer and the writers
+        // stay here.
+        for (size, added) in Size::ALL.into_iter().zip(added) {
+            for (position, species, result) in added {
+                if let Some(observer) = &self.observer {
+                    observer.0.borrow_mut().on_stem(species.to_char(), size);
+                }
+                match result {
+                    Ok((depleted, bouquets)) => self.report(size, depleted, bouquets),
+                    Err(error) => errors.push((position, error)),
+                }
+            }
+        }
+        errors.sort_by_key(|(position, _)| *position);
+        if !collect_errors {
+            errors.truncate(1);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+    /// Reports the depleted species of the bouquets of `size`, and then emits them.
+    fn emit_all(&mut self, size: Size, bouquets: Vec<Bouquet>) {
+        let depleted = self.production_lines.get_mut(size).take_depleted();
+        self.report(size, depleted, bouquets);
+    }
+    fn report(&mut self, size: Size, depleted: Vec<char>, bouquets: Vec<Bouquet>) {
+        if let Some(observer) = &self.observer {
+            for species in depleted {
+                observer.0.borrow_mut().on_deplete(species, size);
+            }
+        }
+        for bouquet in bouquets {
+            self.emit(bouquet);
+        }
+    }
+    fn emit(&mut self, bouquet: Bouquet) {
+        if let Some(observer) = &self.observer {
+            observer.0.borrow_mut().on_bouquet(&bouquet);
+        }
+        self.bouquet_count += 1;
+        if let Some(on_bouquet) = &mut self.on_bouquet.0 {
+            on_bouquet(&bouquet);
+        } else {
+            let writer = self.writers.get_mut(bouquet.size);
+            let design = self.production_lines.get(bouquet.size).design(bouquet.name);
+            let written = match design {
+                Some(design) => bouquet.write_for_design(writer, &self.output_config, design),
+                None => bouquet.write_to(writer, &self.output_config),
+            }
+            .and_then(|()| match self.output_config.latency {
+                Latency::Low => writer.flush(),
+                Latency::Throughput => Ok(()),
+            });
+            if let Err(error) = written {
+                self.write_error.get_or_insert(error);
+            }
+        }
+        if let Some(history) = &mut self.history {
+            history.push(bouquet);
+        }
+    }
+    /// Makes every bouquet that is possible from the stock of all sizes.
+    pub fn drain(&mut self) {
+        for size in Size::ALL {
+            let bouquets = self.production_lines.get_mut(size).drain();
+            self.emit_all(size, bouquets);
+        }
+    }
+    /// Ends the stem stream: makes every bouquet that is possible from the stock, also
+    /// of paused sizes and of stems that didn't trigger a design, and flushes the
+    /// writers. Calling it again makes nothing more, as the first call left no bouquet
+    /// possible.
+    pub fn finalize(&mut self) -> io::Result<()> {
+        self.drain();
+        self.flush()
+    }
+    /// Stops making bouquets of `size`; its stems accumulate until `resume_size`.
+    pub fn pause_size(&mut self, size: Size) {
+        self.production_lines.get_mut(size).pause();
+    }
+    /// Resumes making bouquets of `size`, and writes the bouquets that can be made from
+    /// the stems accumulated while paused.
+    pub fn resume_size(&mut self, size: Size) {
+        let bouquets = self.production_lines.get_mut(size).resume();
+        self.emit_all(size, bouquets);
+    }
+}
+
+/// Reads the next line of `reader` into `line`, replacing its contents, or returns
+/// `false` at the end of the reader. Unlike `BufRead::lines`, this reuses the buffer of
+/// `line` instead of allocating every line, and keeps the line ending, which
+/// `process_line` trims.
+pub(crate) fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<bool> {
+    line.clear();
+    Ok(reader.read_line(line)? != 0)
+}
+
+/// The species of a `#species=` header, either a count of species or the species
+/// themselves.
+fn parse_alphabet(alphabet: &str) -> Result<[bool; SPECIES], InputError> {
+    let invalid = || InputError::InvalidHeader(alphabet.to_owned());
+    let mut species = [false; SPECIES];
+    if let Ok(count) = alphabet.parse::<usize>() {
+        if count > SPECIES {
+            return Err(invalid());
+        }
+        species[..count].fill(true);
+    } else {
+        for value in alphabet.chars() {
+            let Some(value) = char_to_stem_index(value) else {
+                return Err(invalid());
+            };
+            species[value.index()] = true;
+        }
+    }
+    Ok(species)
+}
+
+/// Makes the bouquets of the `stems` lines following the `designs` lines, in the order
+/// they are made. Invalid design and stem lines are skipped.
+///
+/// ```
+/// let bouquets = ranger::run(&["AL5a5"], &["aL"; 5]);
+/// assert_eq!(bouquets, ["AL5a"]);
+/// ```
+pub fn run(designs: &[&str], stems: &[&str]) -> Vec<String> {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    // The errors are skipped lines, which are left out like the binary does.
+    let _ = warehouse.add_designs(designs.iter().copied(), true);
+    let _ = warehouse.add_stems(stems.iter().copied(), true);
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    String::from_utf8_lossy(&output)
+        .lines()
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Runs `stems` on a copy of each catalog, leaving the warehouses untouched.
+pub fn compare_catalogs<W: Write>(catalogs: &[Warehouse<W>], stems: &[(char, Size)]) -> Vec<Stats> {
+    catalogs
+        .iter()
+        .map(|catalog| simulate(&mut catalog.production_lines.clone(), stems))
+        .collect()
+}
+
+fn simulate(production_lines: &mut SizeMap<ProductionLine>, stems: &[(char, Size)]) -> Stats {
+    let mut stats = Stats::default();
+    // Preprocessed up front, as a strict line refuses stems otherwise.
+    for production_line in production_lines.values_mut() {
+        if !production_line.preprocessed {
+            production_line.preprocess();
+        }
+    }
+    for (species, size) in stems {
+        // Like an invalid stem line, a value that is not a species is skipped.
+        let Some(species) = Species::from_char(*species) else {
+            continue;
+        };
+        let production_line = production_lines.get_mut(*size);
+        stats.stems += 1;
+        match production_line.add_stem(species) {
+            Ok(Some(_)) => stats.bouquets += 1,
+            Ok(None) => {}
+            Err(_) => stats.rejected += 1,
+        }
+    }
+    stats.leftover = production_lines
+        .values()
+        .flat_map(|production_line| production_line.stems.as_array())
+        .map(|amount| u32::from(*amount))
+        .sum();
+    stats
+}
+
+/// A stem added on the thread of its size, with its position in the lines, and the
+/// species depleted by and the bouquets made of it.
+type Added = (usize, Species, Result<(Vec<char>, Vec<Bouquet>), StemError>);
+
+fn add_to_line(
+    production_line: &mut ProductionLine,
+    species: Species,
+    cascade: bool,
+) -> Result<Vec<Bouquet>, StemError> {
+    if cascade {
+        production_line.add_stem_cascading(species)
+    } else {
+        Ok(production_line.add_stem(species)?.into_iter().collect())
+    }
+}
+
+/// Inserts `default_size` after the name of a design line that has no size.
+fn with_default_size(design_str: &str, default_size: Option<Size>) -> Cow<'_, str> {
+    // The size is the run of uppercase letters after the name, as the stems start with a
+    // digit.
+    let has_size = design_str
+        .get(1..)
+        .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_uppercase()));
+    match default_size {
+        Some(size) if !has_size && design_str.is_char_boundary(1) => {
+            let (name, rest) = design_str.split_at(1);
+            Cow::Owned(format!("{name}{size}{rest}"))
+        }
+        _ => Cow::Borrowed(design_str),
+    }
+}
+
+/// Parses a design line, which may only use the species of `alphabet`.
+fn parse_design(
+    design_str: &str,
+    default_size: Option<Size>,
+    alphabet: &[bool; SPECIES],
+) -> Result<Design, DesignError> {
+    let design = Design::from_str(&with_default_size(design_str, default_size))
+        .map_err(DesignError::Parse)?;
+    for species in Species::all() {
+        if design.max_stems[species] != 0 && !alphabet[species.index()] {
+            return Err(DesignError::UnknownSpecies(species.to_char()));
+        }
+    }
+    Ok(design)
+}
+
+/// Calls `add` with every line, stopping at the first error unless `collect_errors`.
+fn collect<'a, E>(
+    lines: impl IntoIterator<Item = &'a str>,
+    collect_errors: bool,
+    mut add: impl FnMut(&'a str) -> Result<(), E>,
+) -> Result<(), Vec<(usize, E)>> {
+    let mut errors = Vec::new();
+    for (position, line) in lines.into_iter().enumerate() {
+        if let Err(error) = add(line) {
+            errors.push((position, error));
+            if !collect_errors {
+                break;
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
@@ -0,0 +1,32 @@
+use ranger::{InputError, Size, StemError, Strategy, WarehouseBuilder, Writers};
+
+#[test]
+fn builder_applies_the_settings() {
+    let mut warehouse = WarehouseBuilder::default()
+        .writer(Vec::new())
+        .strategy(Strategy::MinimizeExcess)
+        .capacity(Size::Small, 3)
+        .build()
+        .unwrap();
+    warehouse
+        .process("AS1a1b2\nBS1a1b1c3\n\ncS\naS\nbS\n".as_bytes())
+        .unwrap();
+    assert_eq!(warehouse.add_stem("aS"), Ok(()));
+    assert_eq!(warehouse.add_stem("aS"), Ok(()));
+    assert_eq!(warehouse.add_stem("aS"), Ok(()));
+    assert_eq!(warehouse.add_stem("aS"), Err(StemError::Overflow('a')));
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), "BS1a1b1c\n");
+}
+
+#[test]
+fn builder_rejects_an_invalid_alphabet() {
+    let error = WarehouseBuilder::default()
+        .writer(Vec::new())
+        .alphabet("a1")
+        .build()
+        .unwrap_err();
+    assert_eq!(error, InputError::InvalidHeader("a1".to_owned()));
+}
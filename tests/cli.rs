@@ -0,0 +1,141 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn ranger(args: &[&str], input: &str) -> (String, String) {
+    let (stdout, stderr, _) = ranger_with_code(args, input);
+    (stdout, stderr)
+}
+
+fn ranger_with_code(args: &[&str], input: impl AsRef<[u8]>) -> (String, String, Option<i32>) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ranger"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_ref())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+        output.status.code(),
+    )
+}
+
+#[test]
+fn stats_report_the_throughput_on_stderr() {
+    let input = "AS1a1\nBS2b2\n\naS\nbS\nbS\ncS\n";
+    let (stdout, stderr) = ranger(&["--stats"], input);
+    assert_eq!(stdout, ranger(&[], input).0);
+    assert_eq!(stdout, "AS1a\nBS2b\n");
+    let throughput = stderr.lines().last().unwrap();
+    assert!(
+        throughput.starts_with("4 stems, 2 bouquets in "),
+        "{throughput}"
+    );
+    assert!(throughput.ends_with(" stems/s"), "{throughput}");
+}
+
+#[test]
+fn stats_without_stems_report_no_throughput() {
+    let (_, stderr) = ranger(&["--stats"], "AS1a1\n\n");
+    let throughput = stderr.lines().last().unwrap();
+    assert!(
+        throughput.starts_with("0 stems, 0 bouquets in "),
+        "{throughput}"
+    );
+    assert!(throughput.ends_with(", 0 stems/s"), "{throughput}");
+}
+
+#[test]
+fn exit_code_tells_input_errors_from_io_errors() {
+    let (stdout, stderr, code) = ranger_with_code(&[], "AS1a1\n\naS\n");
+    assert_eq!(
+        (stdout.as_str(), stderr.as_str(), code),
+        ("AS1a\n", "", Some(0))
+    );
+    let (_, stderr, code) = ranger_with_code(&[], "\naS\n");
+    assert_eq!(code, Some(65));
+    assert_eq!(stderr, "Invalid input: EmptyInput\n");
+    let (_, stderr, code) = ranger_with_code(&["--designs", "no/such/designs.txt"], "");
+    assert_eq!(code, Some(74));
+    assert!(
+        stderr.starts_with("I/O error: can't open no/such/designs.txt: "),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn malformed_designs_and_stems_are_input_errors() {
+    let (_, stderr, code) = ranger_with_code(&[], "xx\n\naS\n");
+    assert_eq!(code, Some(65));
+    assert_eq!(stderr, "Invalid input: Design(Parse(InvalidName(\"x\")))\n");
+    let (stdout, stderr, code) = ranger_with_code(&[], "AS1a1\n\n#zz\naS\n");
+    assert_eq!(code, Some(65));
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "Invalid input: Stem(Parse(UnknownSpecies('#')))\n");
+}
+
+#[test]
+fn skip_invalid_warns_and_goes_on() {
+    let (stdout, stderr, code) = ranger_with_code(&["--skip-invalid"], "xx\nAS1a1\n\n#zz\naS\n");
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "AS1a\n");
+    assert_eq!(
+        stderr,
+        "Skipping design \"xx\": Parse(InvalidName(\"x\"))\n\
+         Skipping stem \"#zz\": Parse(UnknownSpecies('#'))\n"
+    );
+}
+
+#[test]
+fn check_exits_like_other_input_errors() {
+    let (_, _, code) = ranger_with_code(&["--check"], "AS1a1\n\naS\n");
+    assert_eq!(code, Some(0));
+    let (_, stderr, code) = ranger_with_code(&["--check"], "#species=a1\nAS1a1\n");
+    assert_eq!(code, Some(65));
+    assert_eq!(stderr, "Invalid design \"#species=a1\": InvalidHeader\n");
+}
+
+#[test]
+fn input_that_is_not_utf8_is_an_io_error() {
+    let (stdout, stderr, code) = ranger_with_code(&[], b"AS1a1\n\naS\n\xff\n");
+    assert_eq!(code, Some(74));
+    assert_eq!(stdout, "AS1a\n");
+    assert!(stderr.starts_with("I/O error: "), "{stderr}");
+}
+
+#[test]
+fn unused_stems_are_warned_once() {
+    let (stdout, stderr) = ranger(&[], "AS1a1\n\ncS\naS\ncS\ncL\n");
+    assert_eq!(stdout, "AS1a\n");
+    assert_eq!(
+        stderr,
+        "No design of size S uses stem c, it stays in stock\n\
+         No design of size L uses stem c, it stays in stock\n"
+    );
+}
+
+#[test]
+fn leftovers_are_printed_as_json() {
+    let (_, stderr) = ranger(&["--leftovers", "json"], "AL5a5b5c15\n\naL\n3cL\n3bL\n");
+    let report: serde_json::Value = serde_json::from_str(stderr.trim()).unwrap();
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "S": [],
+            "L": [
+                {"species": "b", "amount": 3},
+                {"species": "c", "amount": 3},
+                {"species": "a", "amount": 1},
+            ],
+            "XL": [],
+        })
+    );
+}
@@ -0,0 +1,487 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ranger::{
+    char_to_stem_index, AddOutcome, Design, DesignError, DesignParseError, Duplicates, InputError,
+    LineJoiner, ProductionLine, RegisterError, Size, StemCount, Warehouse,
+};
+
+fn parse(design: &str) -> Design {
+    design.parse().unwrap()
+}
+
+fn max(design: &Design, species: char) -> StemCount {
+    design.max_stems()[char_to_stem_index(species).unwrap()]
+}
+
+fn min(design: &Design, species: char) -> StemCount {
+    design.min_stems()[char_to_stem_index(species).unwrap()]
+}
+
+#[test]
+fn parses_name_size_and_total() {
+    let design = parse("AL10a5b12");
+    assert_eq!(design.name(), 'A');
+    assert_eq!(design.size(), ranger::Size::Large);
+    assert_eq!(design.total(), 12);
+}
+
+#[test]
+fn parses_multi_letter_size() {
+    let design = parse("AXL10a5b12");
+    assert_eq!(design.size(), ranger::Size::ExtraLarge);
+    assert_eq!(max(&design, 'a'), 10);
+    assert_eq!(design.total(), 12);
+}
+
+#[test]
+fn caps_max_at_total() {
+    let design = parse("AL10a5");
+    assert_eq!(max(&design, 'a'), 5);
+}
+
+#[test]
+fn caps_max_to_leave_room_for_other_species() {
+    // Every other species needs at least one stem: 1 + 10 - 3 = 8.
+    let design = parse("AL10a1b1c10");
+    assert_eq!(max(&design, 'a'), 8);
+    assert_eq!(max(&design, 'b'), 1);
+    assert_eq!(max(&design, 'c'), 1);
+}
+
+#[test]
+fn single_species_min_is_total() {
+    let design = parse("AL5a5");
+    assert_eq!(min(&design, 'a'), 5);
+    assert_eq!(max(&design, 'a'), 5);
+}
+
+#[test]
+fn min_is_max_minus_other_maxima() {
+    // a: 8 - (1 + 1) = 6, b and c can't go below 1.
+    let design = parse("AL10a1b1c10");
+    assert_eq!(min(&design, 'a'), 6);
+    assert_eq!(min(&design, 'b'), 1);
+    assert_eq!(min(&design, 'c'), 1);
+}
+
+#[test]
+fn min_is_computed_from_the_capped_maxima() {
+    // a is capped at 1 + 5 - 2 = 4 first, so its minimum is 4 - 1 = 3.
+    let design = parse("BL5a1b5");
+    assert_eq!(max(&design, 'a'), 4);
+    assert_eq!(min(&design, 'a'), 3);
+    assert_eq!(min(&design, 'b'), 1);
+}
+
+#[test]
+fn min_is_one_when_other_species_can_fill_the_bouquet() {
+    let design = parse("AL3a3b2c6");
+    for species in ['a', 'b', 'c'] {
+        assert_eq!(min(&design, species), 1);
+    }
+}
+
+#[test]
+fn heavily_skewed_design() {
+    let design = parse("AL200a1b1c200");
+    assert_eq!(min(&design, 'a'), 196);
+    assert_eq!(max(&design, 'a'), 198);
+    assert_eq!(min(&design, 'b'), 1);
+    assert_eq!(min(&design, 'c'), 1);
+}
+
+#[cfg(not(feature = "u8-stems"))]
+#[test]
+fn maxima_adding_up_past_a_stem_total() {
+    let design = parse("AL65000a65000b65001");
+    assert_eq!(min(&design, 'a'), 1);
+    assert_eq!(min(&design, 'b'), 1);
+    let design = parse("AL65000a65000b1c65001");
+    assert_eq!(min(&design, 'a'), 1);
+    assert_eq!(min(&design, 'c'), 1);
+}
+
+#[test]
+fn total_larger_than_any_species_cap() {
+    let design = parse("AL5a5b8");
+    assert_eq!(max(&design, 'a'), 5);
+    assert_eq!(max(&design, 'b'), 5);
+    assert_eq!(min(&design, 'a'), 1);
+    assert_eq!(min(&design, 'b'), 1);
+}
+
+#[test]
+fn unused_species_have_no_min_or_max() {
+    let design = parse("AL5a5");
+    assert_eq!(min(&design, 'b'), 0);
+    assert_eq!(max(&design, 'b'), 0);
+}
+
+#[test]
+fn percentage_max_is_share_of_total() {
+    let design = parse("AL60%a10");
+    assert_eq!(max(&design, 'a'), 6);
+}
+
+#[test]
+fn species_without_max_can_fill_the_total() {
+    let design = parse("ALab3");
+    assert_eq!(max(&design, 'a'), 2);
+    assert_eq!(max(&design, 'b'), 2);
+    assert_eq!(min(&design, 'a'), 1);
+    assert_eq!(design.to_string(), "AL2a2b3");
+    assert_eq!(parse("AL2ab3"), parse("ALab3"));
+    let design = parse("AL1ab3");
+    assert_eq!(max(&design, 'a'), 1);
+    assert_eq!(max(&design, 'b'), 2);
+    assert_eq!(parse("ALa5"), parse("AL5a5"));
+}
+
+#[test]
+fn parses_price() {
+    assert_eq!(parse("AL5a5$12").price(), 12);
+    assert_eq!(parse("AL5a5").price(), 0);
+}
+
+#[test]
+fn displays_the_normalized_design() {
+    assert_eq!(parse("AL10a5").to_string(), "AL5a5");
+    assert_eq!(parse("BL5a1b5$12").to_string(), "BL4a1b5$12");
+    assert_eq!(parse("CXL3b2a6").to_string(), "CXL2a3b6");
+}
+
+#[test]
+fn display_round_trips() {
+    for line in ["AL5a5", "BL4a1b5$12", "CXL2a3b6", "DS8a1b1c10"] {
+        let design = parse(line);
+        assert_eq!(design.to_string(), line);
+        assert_eq!(parse(&design.to_string()), design);
+    }
+}
+
+#[test]
+fn new_rejects_invalid_stems() {
+    let new = |stems: &[(char, StemCount)], total| Design::new('A', Size::Large, stems, total);
+    assert_eq!(
+        new(&[('a', 5), ('?', 5)], 10),
+        Err(DesignParseError::UnknownSpecies('?'))
+    );
+    assert_eq!(new(&[], 5), Err(DesignParseError::NoSpecies));
+    assert_eq!(
+        new(&[('a', 3), ('a', 4)], 5),
+        Err(DesignParseError::DuplicateSpecies('a'))
+    );
+    assert_eq!(
+        new(&[('a', 5), ('b', 5)], 1),
+        Err(DesignParseError::TooFewStems)
+    );
+    assert_eq!(new(&[('a', 5), ('b', 5)], 10), Ok(parse("AL5a5b10")));
+}
+
+#[test]
+fn new_rejects_invalid_names() {
+    for name in ['a', '"', '5', 'É'] {
+        assert_eq!(
+            Design::new(name, Size::Large, &[('a', 5)], 5),
+            Err(DesignParseError::InvalidName(name.to_string())),
+            "{name:?}"
+        );
+    }
+}
+
+#[test]
+fn rejects_malformed_designs() {
+    let error = |design: &str| design.parse::<Design>().unwrap_err();
+    assert_eq!(error(""), DesignParseError::NoMatch);
+    assert_eq!(
+        error("aL5a5"),
+        DesignParseError::InvalidName("a".to_owned())
+    );
+    assert_eq!(
+        error("al10a5"),
+        DesignParseError::InvalidName("a".to_owned())
+    );
+    assert_eq!(
+        error("AB L10a5"),
+        DesignParseError::InvalidName("AB".to_owned())
+    );
+    assert_eq!(
+        error("ABL10a5"),
+        DesignParseError::InvalidName("AB".to_owned())
+    );
+    assert_eq!(
+        error("ABXL10a5"),
+        DesignParseError::InvalidName("AB".to_owned())
+    );
+    assert_eq!(
+        error("5L10a5"),
+        DesignParseError::InvalidName("5".to_owned())
+    );
+    assert_eq!(error("A L10a5"), DesignParseError::Whitespace);
+    assert_eq!(error("AL 10a 5"), DesignParseError::Whitespace);
+    assert_eq!(error("AL5a"), DesignParseError::MissingTotal);
+    assert_eq!(
+        error("AX5a5"),
+        DesignParseError::InvalidSize("X".to_owned())
+    );
+    assert_eq!(error("AL99999a5"), DesignParseError::BadStemCount);
+    assert_eq!(error("AL0"), DesignParseError::NoSpecies);
+    assert_eq!(
+        error("AL0a5b5"),
+        DesignParseError::InconsistentConstraints('a')
+    );
+    assert_eq!(error("AL5a5b1"), DesignParseError::TooFewStems);
+    assert_eq!(error("AS10a0"), DesignParseError::TooFewStems);
+}
+
+#[test]
+fn rejects_duplicate_species() {
+    assert_eq!(
+        "AL3a4a5".parse::<Design>(),
+        Err(DesignParseError::DuplicateSpecies('a'))
+    );
+    assert_eq!(
+        "AL3a4b2a9".parse::<Design>(),
+        Err(DesignParseError::DuplicateSpecies('a'))
+    );
+}
+
+#[test]
+fn distinct_species_parse() {
+    let design = parse("AL3a4b5");
+    assert_eq!(max(&design, 'a'), 3);
+    assert_eq!(max(&design, 'b'), 4);
+}
+
+#[test]
+fn rejects_designs_with_fewer_stems_than_species() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    for (design, outcome) in [
+        ("AL5a5", AddOutcome::Registered),
+        ("BL1a1", AddOutcome::Registered),
+        ("CL2a2b2", AddOutcome::Registered),
+    ] {
+        assert_eq!(warehouse.add_design(design), Ok(outcome), "{design}");
+    }
+    for design in ["DL1a1b1", "EL1a1b1c2"] {
+        assert_eq!(
+            warehouse.add_design(design),
+            Err(DesignError::Parse(DesignParseError::TooFewStems)),
+            "{design}"
+        );
+    }
+}
+
+#[test]
+fn skips_designs_that_can_never_be_made() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    for (design, outcome) in [
+        ("AL5a5", AddOutcome::Registered),
+        ("BL10a5", AddOutcome::Registered),
+        ("CL2a3b5", AddOutcome::Registered),
+        ("DL5a10", AddOutcome::Skipped),
+        ("EL2a2b5", AddOutcome::Skipped),
+        ("FL5a5b20", AddOutcome::Skipped),
+    ] {
+        assert_eq!(warehouse.add_design(design), Ok(outcome), "{design}");
+    }
+    assert_eq!(warehouse.designs(Size::Large).count(), 3);
+
+    let skipped = Rc::new(RefCell::new(Vec::new()));
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.on_skipped_design({
+        let skipped = skipped.clone();
+        move |line| skipped.borrow_mut().push(line.to_owned())
+    });
+    warehouse
+        .process("AL5a5\nBL5a10\n\naL\n".as_bytes())
+        .unwrap();
+    assert_eq!(*skipped.borrow(), ["BL5a10"]);
+}
+
+#[test]
+fn identical_designs_are_merged_once() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_duplicates(Duplicates::Merge);
+    for (design, outcome) in [
+        ("AL5a5", AddOutcome::Registered),
+        ("AL5a5", AddOutcome::Duplicate),
+        ("AS5a5", AddOutcome::Registered),
+        ("AL6a6", AddOutcome::Registered),
+    ] {
+        assert_eq!(warehouse.add_design(design), Ok(outcome), "{design}");
+    }
+    assert_eq!(warehouse.designs(Size::Large).count(), 2);
+
+    let mut production_line = ProductionLine::default();
+    production_line.set_duplicates(Duplicates::Merge);
+    let design_id = production_line.add_design(parse("AL5a5")).unwrap();
+    assert_eq!(production_line.add_design(parse("AL5a5")), Ok(design_id));
+    assert_eq!(
+        production_line.design_count(char_to_stem_index('a').unwrap()),
+        1
+    );
+}
+
+#[test]
+fn identical_designs_can_be_rejected() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_duplicates(Duplicates::Reject);
+    assert_eq!(warehouse.add_design("AL5a5"), Ok(AddOutcome::Registered));
+    assert_eq!(
+        warehouse.add_design("AL5a5"),
+        Err(DesignError::Register(RegisterError::Duplicate('A')))
+    );
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    for _ in 0..2 {
+        assert_eq!(warehouse.add_design("AL5a5"), Ok(AddOutcome::Registered));
+    }
+    assert_eq!(warehouse.designs(Size::Large).count(), 2);
+}
+
+#[test]
+fn lists_the_registered_designs_per_size() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    for design in ["AL10a5", "BS3a3", "CL1a1b1", "DL2b2", "EL5a5b5"] {
+        let _ = warehouse.add_design(design);
+    }
+    let designs: Vec<String> = warehouse
+        .designs(Size::Large)
+        .map(Design::to_string)
+        .collect();
+    assert_eq!(designs, ["AL5a5", "DL2b2", "EL4a4b5"]);
+    assert_eq!(warehouse.designs(Size::Small).count(), 1);
+    assert_eq!(warehouse.designs(Size::ExtraLarge).count(), 0);
+}
+
+#[test]
+fn invalid_designs_can_be_skipped() {
+    let invalid = Rc::new(RefCell::new(Vec::new()));
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.on_invalid_design({
+        let invalid = invalid.clone();
+        move |line, error| invalid.borrow_mut().push((line.to_owned(), error.clone()))
+    });
+    warehouse
+        .process("AS1a1\nBS3a4a5\n\naS\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        *invalid.borrow(),
+        vec![(
+            "BS3a4a5".to_owned(),
+            DesignError::Parse(DesignParseError::DuplicateSpecies('a'))
+        )]
+    );
+}
+
+#[test]
+fn continued_lines_make_one_design() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AL10a5b\\\n  3c15\nBS2a\\\n2\\\n\naL\n".as_bytes())
+        .unwrap();
+    let designs: Vec<String> = Size::ALL
+        .into_iter()
+        .flat_map(|size| warehouse.designs(size).map(ToString::to_string))
+        .collect();
+    assert_eq!(designs, ["BS2a2", "AL10a5b3c15"]);
+    assert_eq!(
+        warehouse.designs(Size::Large).next(),
+        Some(&parse("AL10a5b3c15"))
+    );
+}
+
+#[test]
+fn escaped_backslash_does_not_continue_a_line() {
+    let mut joiner = LineJoiner::default();
+    assert_eq!(joiner.push("AL10a\\"), None);
+    assert_eq!(joiner.push("5b3c15").as_deref(), Some("AL10a5b3c15"));
+    assert_eq!(joiner.push("AL5a\\\\").as_deref(), Some("AL5a\\\\"));
+    assert_eq!(joiner.finish(), None);
+    let warehouse = Warehouse::with_writer(Vec::new());
+    let errors = warehouse
+        .validate_designs("AL5a\\\\\nBL5\\\na5\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        errors,
+        [(
+            "AL5a\\\\".to_owned(),
+            DesignError::Parse(DesignParseError::MissingTotal)
+        )]
+    );
+}
+
+#[test]
+fn validation_reports_every_invalid_design() {
+    let warehouse = Warehouse::with_writer(Vec::new());
+    let errors = warehouse
+        .validate_designs("AS1a1\nBS3a4a5\nCL1a1b1\naS\n\ncS\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        errors,
+        [
+            (
+                "BS3a4a5".to_owned(),
+                DesignError::Parse(DesignParseError::DuplicateSpecies('a'))
+            ),
+            (
+                "CL1a1b1".to_owned(),
+                DesignError::Parse(DesignParseError::TooFewStems)
+            ),
+            (
+                "aS".to_owned(),
+                DesignError::Parse(DesignParseError::InvalidName("a".to_owned()))
+            ),
+        ]
+    );
+    let errors = warehouse
+        .validate_designs("#species=ab\nAS1a1\nBS1c1\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        errors,
+        [("BS1c1".to_owned(), DesignError::UnknownSpecies('c'))]
+    );
+    let errors = warehouse
+        .validate_designs("#species=a1\nAS1a1\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        errors,
+        [("#species=a1".to_owned(), DesignError::InvalidHeader)]
+    );
+}
+
+#[test]
+fn validate_designs_takes_the_header_where_process_does() {
+    let input = "\n#species=ab\nAS1a1\nBS1c1\n---\n";
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_separator("---");
+    let errors = warehouse.validate_designs(input.as_bytes()).unwrap();
+    assert_eq!(
+        errors,
+        [("BS1c1".to_owned(), DesignError::UnknownSpecies('c'))]
+    );
+    assert_eq!(
+        warehouse.process(input.as_bytes()),
+        Err(InputError::Design(DesignError::UnknownSpecies('c')))
+    );
+    let errors = warehouse
+        .validate_designs("AS1a1\n#species=ab\n---\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        errors,
+        [(
+            "#species=ab".to_owned(),
+            DesignError::Parse(DesignParseError::InvalidName("#".to_owned()))
+        )]
+    );
+}
+
+#[test]
+fn add_design_returns_the_registration_index() {
+    let mut production_line = ProductionLine::default();
+    for (position, line) in ["AS1a1", "BS1b1", "CS1c1"].into_iter().enumerate() {
+        let design_id = production_line.add_design(parse(line)).unwrap();
+        assert_eq!(design_id.index(), position);
+    }
+}
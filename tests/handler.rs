@@ -0,0 +1,94 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ranger::{AddOutcome, Bouquet, Duplicates, EngineObserver, Size, Warehouse};
+
+#[test]
+fn handler_receives_every_bouquet() {
+    let bouquets = Rc::new(RefCell::new(Vec::new()));
+    let mut warehouse = Warehouse::with_handler({
+        let bouquets = bouquets.clone();
+        move |bouquet| bouquets.borrow_mut().push(bouquet.clone())
+    });
+    warehouse
+        .process("AS1a1b2\nBL2a2\n\naS\naL\nbS\naL\n".as_bytes())
+        .unwrap();
+    let bouquets = bouquets.borrow();
+    let made: Vec<(char, Size)> = bouquets
+        .iter()
+        .map(|bouquet| (bouquet.name, bouquet.size))
+        .collect();
+    assert_eq!(made, vec![('A', Size::Small), ('B', Size::Large)]);
+}
+
+#[test]
+fn handler_replaces_the_writer() {
+    let count = Rc::new(RefCell::new(0));
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.on_bouquet({
+        let count = count.clone();
+        move |_| *count.borrow_mut() += 1
+    });
+    warehouse.process("AS1a1\n\naS\naS\n".as_bytes()).unwrap();
+    assert_eq!(*count.borrow(), 2);
+    let ranger::Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert!(output.is_empty());
+}
+
+struct Recorder(Rc<RefCell<Vec<String>>>);
+impl EngineObserver for Recorder {
+    fn on_stem(&mut self, species: char, size: Size) {
+        self.0.borrow_mut().push(format!("stem {species}{size}"));
+    }
+    fn on_bouquet(&mut self, bouquet: &Bouquet) {
+        self.0.borrow_mut().push(format!("bouquet {bouquet}"));
+    }
+    fn on_reject(&mut self, line: &str) {
+        self.0.borrow_mut().push(format!("reject {line}"));
+    }
+    fn on_merge(&mut self, line: &str) {
+        self.0.borrow_mut().push(format!("merge {line}"));
+    }
+    fn on_deplete(&mut self, species: char, size: Size) {
+        self.0.borrow_mut().push(format!("deplete {species}{size}"));
+    }
+}
+
+#[test]
+fn observer_receives_every_event() {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_observer(Recorder(events.clone()));
+    warehouse.add_design("AS1a1b2").unwrap();
+    warehouse.add_design("A").unwrap_err();
+    warehouse.preprocess();
+    warehouse.add_stem("aS").unwrap();
+    warehouse.add_stem("?S").unwrap_err();
+    warehouse.add_stem("bS").unwrap();
+    assert_eq!(
+        *events.borrow(),
+        [
+            "reject A",
+            "stem aS",
+            "reject ?S",
+            "stem bS",
+            "deplete aS",
+            "deplete bS",
+            "bouquet AS1a1b",
+        ]
+    );
+}
+
+#[test]
+fn merged_designs_are_not_rejections() {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_observer(Recorder(events.clone()));
+    warehouse.set_duplicates(Duplicates::Merge);
+    warehouse.add_design("AS1a1b2").unwrap();
+    assert_eq!(warehouse.add_design("AS1a1b2"), Ok(AddOutcome::Duplicate));
+    warehouse.add_design("A").unwrap_err();
+    assert_eq!(*events.borrow(), ["merge AS1a1b2", "reject A"]);
+}
@@ -0,0 +1,151 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use proptest::prelude::*;
+use ranger::{
+    char_to_stem_index, Bouquet, Design, EngineObserver, ProductionLine, Size, StemCount,
+    Warehouse, Writers,
+};
+
+const SPECIES: &[char] = &['a', 'b', 'c', 'd', 'e', 'f'];
+
+enum Event {
+    Stem(char),
+    Bouquet(Box<Bouquet>),
+}
+
+struct Recorder(Rc<RefCell<Vec<Event>>>);
+impl EngineObserver for Recorder {
+    fn on_stem(&mut self, species: char, _size: Size) {
+        self.0.borrow_mut().push(Event::Stem(species));
+    }
+    fn on_bouquet(&mut self, bouquet: &Bouquet) {
+        self.0
+            .borrow_mut()
+            .push(Event::Bouquet(Box::new(bouquet.clone())));
+    }
+}
+
+/// A design line with distinct species, and a total the maxima can reach.
+fn design_line(name: char) -> impl Strategy<Value = String> {
+    prop::sample::subsequence(SPECIES, 1..=SPECIES.len())
+        .prop_flat_map(|species| {
+            let maxima = prop::collection::vec(1u16..8, species.len());
+            (Just(species), maxima)
+        })
+        .prop_flat_map(move |(species, maxima)| {
+            let sum: u16 = maxima.iter().sum();
+            let unique = species.len() as u16;
+            (Just(species), Just(maxima), unique..=sum)
+        })
+        .prop_map(move |(species, maxima, total)| {
+            let stems: String = species
+                .iter()
+                .zip(&maxima)
+                .map(|(species, max)| format!("{max}{species}"))
+                .collect();
+            format!("{name}L{stems}{total}")
+        })
+}
+
+fn catalog() -> impl Strategy<Value = Vec<String>> {
+    (1usize..6).prop_flat_map(|count| {
+        (0..count)
+            .map(|index| design_line((b'A' + index as u8) as char))
+            .collect::<Vec<_>>()
+    })
+}
+
+/// Parses a bouquet line like `AL3a2b` into the design name and the amount per species.
+fn parse_bouquet(line: &str) -> (char, HashMap<char, StemCount>) {
+    let mut chars = line.chars();
+    let name = chars.next().unwrap();
+    assert_eq!(chars.next(), Some('L'));
+    let mut stems = HashMap::new();
+    let mut amount = 0;
+    for value in chars {
+        match value.to_digit(10) {
+            Some(digit) => amount = amount * 10 + digit as StemCount,
+            None => {
+                stems.insert(value, amount);
+                amount = 0;
+            }
+        }
+    }
+    (name, stems)
+}
+
+proptest! {
+    #[test]
+    fn bouquets_satisfy_their_design(
+        designs in catalog(),
+        stems in prop::collection::vec(prop::sample::select(SPECIES), 0..300),
+    ) {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut warehouse = Warehouse::with_writer(Vec::new());
+        warehouse.set_observer(Recorder(events.clone()));
+        let mut input = designs.join("\n");
+        input.push_str("\n\n");
+        for species in &stems {
+            input.push_str(&format!("{species}L\n"));
+        }
+        warehouse.process(input.as_bytes()).unwrap();
+        let Writers::Shared(output) = warehouse.into_writers() else {
+            unreachable!();
+        };
+
+        let designs: HashMap<char, Design> = designs
+            .iter()
+            .map(|line| {
+                let design: Design = line.parse().unwrap();
+                (design.name(), design)
+            })
+            .collect();
+        for line in String::from_utf8(output).unwrap().lines() {
+            let (name, bouquet) = parse_bouquet(line);
+            let design = &designs[&name];
+            let mut total = 0;
+            for species in SPECIES {
+                let amount = bouquet.get(species).copied().unwrap_or(0);
+                let stem_index = char_to_stem_index(*species).unwrap();
+                prop_assert!(amount >= design.min_stems()[stem_index], "{line}");
+                prop_assert!(amount <= design.max_stems()[stem_index], "{line}");
+                total += u32::from(amount);
+            }
+            prop_assert_eq!(total, design.total(), "{}", line);
+        }
+
+        let mut stock = [0i32; ranger::SPECIES];
+        for event in events.borrow().iter() {
+            match event {
+                Event::Stem(species) => stock[char_to_stem_index(*species).unwrap().index()] += 1,
+                Event::Bouquet(bouquet) => {
+                    for (stem_index, stock) in stock.iter_mut().enumerate() {
+                        *stock -= i32::from(bouquet.stems[stem_index]);
+                        prop_assert!(*stock >= 0, "{}", bouquet);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn peek_predicts_commit(
+        designs in catalog(),
+        stems in prop::collection::vec(prop::sample::select(SPECIES), 0..300),
+    ) {
+        let mut production_line = ProductionLine::default();
+        for line in &designs {
+            production_line.add_design(line.parse().unwrap()).unwrap();
+        }
+        for species in stems {
+            let species = char_to_stem_index(species).unwrap();
+            let stock = production_line.stems();
+            let peeked = production_line.peek_add_stem(species);
+            prop_assert_eq!(production_line.stems(), stock);
+            let committed = production_line.commit_add_stem(species).unwrap();
+            prop_assert_eq!(peeked, committed);
+        }
+    }
+}
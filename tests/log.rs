@@ -0,0 +1,62 @@
+#![cfg(feature = "log")]
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use ranger::{char_to_stem_index, Design, ProductionLine};
+
+static RECORDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct Recorder;
+impl Log for Recorder {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Trace
+    }
+    fn log(&self, record: &Record) {
+        RECORDS.lock().unwrap().push(record.args().to_string());
+    }
+    fn flush(&self) {}
+}
+
+#[test]
+fn traces_the_decision_path_of_a_stem() {
+    log::set_logger(&Recorder).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+    let mut production_line = ProductionLine::default();
+    for design in ["AS4a4b4c6", "BS5a5"] {
+        production_line
+            .add_design(design.parse::<Design>().unwrap())
+            .unwrap();
+    }
+    production_line.preprocess();
+    production_line.pause();
+    for species in ['a', 'a', 'b'] {
+        production_line
+            .add_stem(char_to_stem_index(species).unwrap())
+            .unwrap();
+    }
+    assert_eq!(
+        std::mem::take(&mut *RECORDS.lock().unwrap()),
+        [
+            "stem a, 1 in stock",
+            "stem a, 2 in stock",
+            "stem b, 1 in stock"
+        ]
+    );
+    for species in ['a', 'a', 'b'] {
+        production_line
+            .add_stem(char_to_stem_index(species).unwrap())
+            .unwrap();
+    }
+    RECORDS.lock().unwrap().clear();
+    assert!(production_line
+        .try_make_design(char_to_stem_index('a').unwrap())
+        .is_none());
+    assert_eq!(
+        *RECORDS.lock().unwrap(),
+        [
+            "considering designs \"BA\" for stem a",
+            "design B skipped: below the minimum of a",
+            "design A skipped: below the minimum of c",
+        ]
+    );
+}
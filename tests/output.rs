@@ -0,0 +1,208 @@
+use std::io::{self, BufReader, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use ranger::{
+    vec_to_stems, Bouquet, InputError, Latency, OutputConfig, OutputFormat, Size, SpeciesOrder,
+    Tee, Warehouse, Writers,
+};
+
+fn write(format: OutputFormat) -> String {
+    write_with(OutputConfig {
+        format,
+        ..OutputConfig::default()
+    })
+}
+
+fn write_with(config: OutputConfig) -> String {
+    let bouquet = Bouquet {
+        name: 'A',
+        size: Size::Large,
+        stems: vec_to_stems(&[('a', 5), ('c', 2)]).unwrap(),
+    };
+    let mut out = Vec::new();
+    bouquet.write_to(&mut out, &config).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn compact_is_the_bouquet_line() {
+    assert_eq!(write(OutputFormat::Compact), "AL5a2c\n");
+}
+
+#[test]
+fn json_is_an_object_per_line() {
+    let line = write(OutputFormat::Json);
+    assert_eq!(
+        line,
+        "{\"design\":\"A\",\"size\":\"L\",\"stems\":{\"a\":5,\"c\":2}}\n"
+    );
+    let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(value["stems"]["a"], 5);
+}
+
+#[test]
+fn custom_order_lists_repeated_species_once() {
+    let config = OutputConfig {
+        species_order: SpeciesOrder::Custom(vec!['c', '?', 'c', 'a', 'c']),
+        ..OutputConfig::default()
+    };
+    assert_eq!(write_with(config), "AL2c5a\n");
+}
+
+#[test]
+fn descending_max_orders_by_the_maximums_of_the_design() {
+    let run = |species_order| {
+        let mut warehouse = Warehouse::with_writer(Vec::new());
+        warehouse.set_output_config(OutputConfig {
+            species_order,
+            ..OutputConfig::default()
+        });
+        warehouse.add_designs(["AL2a5b3c6"], false).unwrap();
+        warehouse
+            .add_stems(["aL", "aL", "cL", "cL", "cL", "bL"], false)
+            .unwrap();
+        let Writers::Shared(output) = warehouse.into_writers() else {
+            unreachable!()
+        };
+        String::from_utf8(output).unwrap()
+    };
+    assert_eq!(run(SpeciesOrder::Ascending), "AL2a1b3c\n");
+    assert_eq!(run(SpeciesOrder::DescendingMax), "AL1b3c2a\n");
+    assert_eq!(run(SpeciesOrder::DescendingAmount), "AL3c2a1b\n");
+}
+
+#[test]
+fn csv_has_a_column_per_field() {
+    assert_eq!(OutputFormat::Csv.header(), Some("design,size,stems"));
+    assert_eq!(write(OutputFormat::Csv), "A,L,5a2c\n");
+}
+
+#[test]
+fn tee_writes_the_same_bytes_to_both_writers() {
+    let mut warehouse = Warehouse::with_writer(Tee(Vec::new(), Vec::new()));
+    warehouse
+        .process("AL2a2\nBS1b1\n\naL\nbS\naL\n".as_bytes())
+        .unwrap();
+    let Writers::Shared(Tee(first, second)) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(first.clone()).unwrap(), "BS1b\nAL2a\n");
+    assert_eq!(first, second);
+}
+
+struct Broken;
+impl Write for Broken {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("broken"))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn tee_names_the_failed_writer() {
+    let mut tee = Tee(Vec::new(), Broken);
+    let error = tee.write_all(b"AL2a\n").unwrap_err();
+    assert_eq!(error.to_string(), "second writer: broken");
+    assert_eq!(tee.0, b"AL2a\n");
+    let error = Tee(Broken, Broken).write(b"AL2a\n").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "first writer: broken, second writer: broken"
+    );
+}
+
+#[test]
+fn failed_writes_are_returned_instead_of_panicking() {
+    let mut warehouse = Warehouse::with_writer(Broken);
+    let error = warehouse.process("AS1a1\n\naS\n".as_bytes()).unwrap_err();
+    assert!(matches!(error, InputError::Io(_)), "{error:?}");
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    let error = warehouse.process(&b"AS1a1\n\n\xff\n"[..]).unwrap_err();
+    assert_eq!(error, InputError::Io(io::ErrorKind::InvalidData.into()));
+}
+
+#[test]
+fn history_is_written_as_csv() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_keep_history(true);
+    warehouse
+        .process("AS1a1b2\nBL2c2\n\naS\ncL\nbS\ncL\n".as_bytes())
+        .unwrap();
+    let mut csv = Vec::new();
+    warehouse.write_csv(&mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "name,size,total,a,b,c\nA,S,2,1,1,0\nB,L,2,0,0,2\n"
+    );
+}
+
+/// Counts the flushes of the bouquets written to it.
+#[derive(Default)]
+struct Flushes(usize);
+impl Write for Flushes {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        Ok(bytes.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.0 += 1;
+        Ok(())
+    }
+}
+
+fn flushes(latency: Latency) -> usize {
+    let mut warehouse = Warehouse::with_writer(Flushes::default());
+    warehouse.set_output_config(OutputConfig {
+        latency,
+        ..OutputConfig::default()
+    });
+    warehouse.process("AS1a1\n\naS\naS\n".as_bytes()).unwrap();
+    let Writers::Shared(Flushes(flushes)) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    flushes
+}
+
+#[test]
+fn low_latency_flushes_every_bouquet() {
+    assert_eq!(flushes(Latency::Low), 3);
+    assert_eq!(flushes(Latency::Throughput), 1);
+}
+
+/// Returns its chunks one per read, sleeping before every chunk but the first.
+struct Slow(Vec<&'static str>, Duration);
+impl Read for Slow {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.0.is_empty() {
+            return Ok(0);
+        }
+        let chunk = self.0.remove(0);
+        if self.0.len() < 2 {
+            thread::sleep(self.1);
+        }
+        buf[..chunk.len()].copy_from_slice(chunk.as_bytes());
+        Ok(chunk.len())
+    }
+}
+
+#[test]
+fn heartbeats_are_written_while_waiting_for_stems() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_output_config(OutputConfig {
+        heartbeat: Some(Duration::from_millis(10)),
+        ..OutputConfig::default()
+    });
+    let reader = BufReader::new(Slow(
+        vec!["AS1a1\n\n", "aS\n", "\n"],
+        Duration::from_millis(200),
+    ));
+    warehouse.process_streaming(reader).unwrap();
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("# heartbeat\n"), "{output}");
+    assert!(output.contains("AS1a\n"), "{output}");
+}
@@ -0,0 +1,86 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use ranger::{Size, SizeMap, Tee, Warehouse, Writers};
+
+const DESIGNS: [&str; 5] = ["AS1a1b2", "BS2c2", "AL2a1b3", "BL3c3", "AXL1a1c2"];
+
+fn run(stems: &[&str], parallel: bool) -> SizeMap<String> {
+    let mut warehouse = Warehouse::with_writers(SizeMap::from_fn(|_| Vec::new()));
+    warehouse.set_parallel(parallel);
+    warehouse.add_designs(DESIGNS, false).unwrap();
+    warehouse.add_stems(stems.iter().copied(), false).unwrap();
+    let Writers::PerSize(writers) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    SizeMap::from_fn(|size| String::from_utf8(writers.get(size).clone()).unwrap())
+}
+
+#[test]
+fn parallel_output_per_size_matches_sequential() {
+    let species = ['a', 'b', 'c'];
+    let stems: Vec<String> = (0..300)
+        .map(|index| {
+            let size = Size::ALL[index % Size::ALL.len()];
+            format!("{}{size}", species[index / Size::ALL.len() % species.len()])
+        })
+        .collect();
+    let stems: Vec<&str> = stems.iter().map(String::as_str).collect();
+    let sequential = run(&stems, false);
+    assert_eq!(run(&stems, true), sequential);
+    for size in Size::ALL {
+        assert!(!sequential.get(size).is_empty(), "{size}");
+    }
+}
+
+#[test]
+fn parallel_errors_keep_their_position() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_parallel(true);
+    warehouse.add_designs(DESIGNS, false).unwrap();
+    let errors = warehouse
+        .add_stems(["aS", "?L", "bS", "aXL", "cQ"], true)
+        .unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|(position, _)| *position)
+            .collect::<Vec<_>>(),
+        vec![1, 4]
+    );
+}
+
+#[test]
+fn bouquets_go_to_the_writer_of_their_size() {
+    let output = run(&["aS", "aL", "bS", "aL", "bL"], false);
+    assert_eq!(output.get(Size::Small), "AS1a1b\n");
+    assert_eq!(output.get(Size::Large), "AL2a1b\n");
+    assert_eq!(output.get(Size::ExtraLarge), "");
+}
+
+/// A writer whose bytes can be read while the warehouse owns it.
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn separate_writers_can_be_of_different_types() {
+    let (small, large) = (Shared::default(), Shared::default());
+    let mut warehouse =
+        Warehouse::with_separate_writers(small.clone(), Tee(large.clone(), io::sink()), io::sink());
+    warehouse.add_designs(DESIGNS, false).unwrap();
+    warehouse
+        .add_stems(["aS", "aL", "bS", "aL", "bL", "aXL", "cXL"], false)
+        .unwrap();
+    warehouse.flush().unwrap();
+    assert_eq!(*small.0.borrow(), b"AS1a1b\n");
+    assert_eq!(*large.0.borrow(), b"AL2a1b\n");
+}
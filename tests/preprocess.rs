@@ -0,0 +1,106 @@
+use ranger::{char_to_stem_index, Design, ProductionLine, Size, StemError, Warehouse};
+
+fn design(design: &str) -> Design {
+    design.parse().unwrap()
+}
+
+#[test]
+fn preprocess_is_idempotent() {
+    let mut production_line = ProductionLine::default();
+    for line in ["AS3a1b3", "BS1a1", "CS2a1c2", "DS1b1"] {
+        production_line.add_design(design(line)).unwrap();
+    }
+    let index = production_line.export_index();
+    production_line.preprocess();
+    assert_eq!(production_line.export_index(), index);
+}
+
+#[test]
+fn late_simpler_design_is_prioritized() {
+    let mut production_line = ProductionLine::default();
+    production_line.add_design(design("AS2a2")).unwrap();
+    production_line.preprocess();
+    production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap();
+    production_line.add_design(design("BS1a1")).unwrap();
+    let bouquet = production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap();
+    assert_eq!(bouquet.unwrap().name, 'B');
+}
+
+#[test]
+fn stems_before_preprocess_use_the_prioritized_order() {
+    let mut production_line = ProductionLine::default();
+    production_line.add_design(design("AS2a2")).unwrap();
+    production_line.add_design(design("BS1a1")).unwrap();
+    let bouquet = production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap();
+    assert_eq!(bouquet.unwrap().name, 'B');
+
+    let mut production_line = ProductionLine::default();
+    production_line.add_design(design("AS2a2")).unwrap();
+    production_line.pause();
+    production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap();
+    production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap();
+    production_line.add_design(design("BS1a1")).unwrap();
+    let bouquet = production_line.try_make_design(char_to_stem_index('a').unwrap());
+    assert_eq!(bouquet.unwrap().name, 'B');
+}
+
+#[test]
+fn report_counts_the_designs_per_species() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .add_designs(["AS3a1b3", "BS1a1", "CS2a1c2", "DL4b4"], false)
+        .unwrap();
+    let report = warehouse.preprocess_with_report();
+    let small = &report[Size::Small];
+    let index = |species| char_to_stem_index(species).unwrap().index();
+    assert_eq!(small.designs[index('a')], 3);
+    assert_eq!(small.designs[index('b')], 1);
+    assert_eq!(small.designs[index('c')], 1);
+    assert_eq!(small.designs[index('d')], 0);
+    assert_eq!(small.max_stems[index('a')], 2);
+    assert_eq!(small.max_stems[index('c')], 1);
+    assert_eq!(&small.unused()[..3], ['d', 'e', 'f']);
+    assert_eq!(report[Size::Large].designs[index('b')], 1);
+    assert_eq!(report[Size::Large].max_stems[index('b')], 4);
+    assert_eq!(report[Size::ExtraLarge].unused().len(), ranger::SPECIES);
+}
+
+#[test]
+fn strict_mode_refuses_stems_before_preprocess() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_strict(true);
+    warehouse.add_design("AS1a1").unwrap();
+    assert_eq!(warehouse.add_stem("aS"), Err(StemError::NotPreprocessed));
+    assert_eq!(warehouse.inventory(Size::Small), []);
+    warehouse.preprocess();
+    assert_eq!(warehouse.add_stem("aS"), Ok(()));
+}
+
+#[test]
+fn imported_index_replaces_preprocess() {
+    let line = |designs: &[&str]| {
+        let mut production_line = ProductionLine::default();
+        for line in designs {
+            production_line.add_design(design(line)).unwrap();
+        }
+        production_line
+    };
+    let index = line(&["AS2a2", "BS1a1"]).export_index();
+    let mut production_line = line(&["AS2a2", "BS1a1"]);
+    production_line.import_index(index.clone());
+    assert_eq!(production_line.export_index(), index);
+    let bouquet = production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap();
+    assert_eq!(bouquet.unwrap().name, 'B');
+}
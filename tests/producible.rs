@@ -0,0 +1,370 @@
+use std::sync::{Arc, Mutex};
+
+use ranger::{
+    char_to_stem_index, vec_to_stems, Bouquet, Design, DesignParseError, ProductionLine, Size,
+    StemParseError, Warehouse,
+};
+
+fn production_line(designs: &[&str]) -> ProductionLine {
+    let mut production_line = ProductionLine::default();
+    for design in designs {
+        production_line
+            .add_design(design.parse::<Design>().unwrap())
+            .unwrap();
+    }
+    production_line.preprocess();
+    production_line
+}
+
+#[test]
+fn producible_matches_the_next_bouquet() {
+    let mut production_line = production_line(&["AS1a1b2", "BS2c2"]);
+    production_line.pause();
+    for species in ['a', 'c'] {
+        production_line
+            .add_stem(char_to_stem_index(species).unwrap())
+            .unwrap();
+    }
+    assert!(production_line.producible().is_empty());
+    production_line
+        .add_stem(char_to_stem_index('b').unwrap())
+        .unwrap();
+    assert_eq!(production_line.producible(), vec!['A']);
+    let bouquet = production_line
+        .try_make_design(char_to_stem_index('b').unwrap())
+        .unwrap();
+    assert_eq!(bouquet.name, 'A');
+    assert!(production_line.producible().is_empty());
+}
+
+#[test]
+fn producible_doesnt_take_stems() {
+    let mut production_line = production_line(&["AS2a2"]);
+    production_line.pause();
+    production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap();
+    production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap();
+    assert_eq!(production_line.producible(), vec!['A']);
+    assert_eq!(production_line.producible(), vec!['A']);
+    assert!(production_line
+        .try_make_design(char_to_stem_index('a').unwrap())
+        .is_some());
+}
+
+#[test]
+fn excess_is_returned_evenly_across_species() {
+    let mut production_line = production_line(&["AL3a3b3c6"]);
+    production_line.pause();
+    for species in ['a', 'b', 'c'] {
+        for _ in 0..3 {
+            production_line
+                .add_stem(char_to_stem_index(species).unwrap())
+                .unwrap();
+        }
+    }
+    let bouquet = production_line
+        .try_make_design(char_to_stem_index('c').unwrap())
+        .unwrap();
+    assert_eq!(bouquet.to_string(), "AL2a2b2c");
+}
+
+#[test]
+fn excess_is_returned_from_the_trim_order_first() {
+    let mut production_line = production_line(&["AL3a3b3c6"]);
+    production_line.set_trim_order(vec![
+        char_to_stem_index('c').unwrap(),
+        char_to_stem_index('a').unwrap(),
+    ]);
+    production_line.pause();
+    for species in ['a', 'b', 'c'] {
+        for _ in 0..3 {
+            production_line
+                .add_stem(char_to_stem_index(species).unwrap())
+                .unwrap();
+        }
+    }
+    let bouquet = production_line
+        .try_make_design(char_to_stem_index('c').unwrap())
+        .unwrap();
+    assert_eq!(bouquet.to_string(), "AL2a3b1c");
+}
+
+#[test]
+fn a_species_can_be_used_by_more_designs_than_species() {
+    let mut production_line = ProductionLine::default();
+    for name in 'A'..='Z' {
+        let design = Design::new(name, Size::Large, &[('a', 1), ('b', 1)], 2).unwrap();
+        production_line.add_design(design).unwrap();
+    }
+    // Names are letters too, so the 27th design shares its name.
+    let design = Design::new('S', Size::Large, &[('a', 1)], 1).unwrap();
+    production_line.add_design(design).unwrap();
+    assert_eq!(
+        production_line.design_count(char_to_stem_index('a').unwrap()),
+        27
+    );
+    let bouquet = production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(bouquet.to_string(), "SL1a");
+}
+
+#[test]
+fn next_design_for_matches_the_design_made() {
+    let mut production_line = production_line(&["AS1a1b2", "BS2b2"]);
+    assert_eq!(
+        production_line.next_design_for(char_to_stem_index('b').unwrap()),
+        None
+    );
+    production_line
+        .add_stem(char_to_stem_index('b').unwrap())
+        .unwrap();
+    assert_eq!(
+        production_line.next_design_for(char_to_stem_index('a').unwrap()),
+        Some('A')
+    );
+    let next = production_line.next_design_for(char_to_stem_index('b').unwrap());
+    assert_eq!(next, Some('B'));
+    let bouquet = production_line
+        .add_stem(char_to_stem_index('b').unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(Some(bouquet.name), next);
+}
+
+#[test]
+fn designs_with_a_total_of_zero_are_rejected() {
+    assert_eq!(
+        "AS10a0".parse::<Design>(),
+        Err(DesignParseError::TooFewStems)
+    );
+    assert_eq!(
+        Design::new('A', Size::Small, &[('a', 10)], 0),
+        Err(DesignParseError::TooFewStems)
+    );
+}
+
+#[test]
+fn min_stock_for_full_catalog_reaches_every_total() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .add_designs(["AL5a5b10", "BL3a5b4"], false)
+        .unwrap();
+    assert_eq!(
+        warehouse.min_stock_for_full_catalog(Size::Large),
+        vec![('a', 8), ('b', 6)]
+    );
+    let mut production_line = production_line(&["AL5a5b10"]);
+    let stock = production_line.min_stock_for_full_catalog();
+    assert_eq!(stock[..3], [5, 5, 0]);
+    production_line.pause();
+    for (species, amount) in [('a', stock[0]), ('b', stock[1])] {
+        for _ in 0..amount {
+            production_line
+                .add_stem(char_to_stem_index(species).unwrap())
+                .unwrap();
+        }
+    }
+    assert_eq!(production_line.resume().len(), 1);
+}
+
+#[cfg(not(feature = "u8-stems"))]
+#[test]
+fn min_stock_for_full_catalog_sums_past_a_stem_count() {
+    let designs: Vec<String> = ('A'..='T')
+        .map(|name| format!("{name}L5000a5000"))
+        .collect();
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .add_designs(designs.iter().map(String::as_str), false)
+        .unwrap();
+    assert_eq!(
+        warehouse.min_stock_for_full_catalog(Size::Large),
+        vec![('a', 100_000)]
+    );
+}
+
+#[test]
+fn producible_now_leaves_out_designs_past_their_quota() {
+    let mut production_line = production_line(&["AS1a1", "BS2a2", "CS3a3"]);
+    production_line.set_quota('A', Some(1));
+    production_line.set_quota('C', Some(1));
+    let bouquet = production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(bouquet.name, 'A');
+    production_line.pause();
+    for _ in 0..6 {
+        production_line
+            .add_stem(char_to_stem_index('a').unwrap())
+            .unwrap();
+    }
+    assert_eq!(production_line.producible_now(), ['B', 'C']);
+}
+
+#[test]
+fn best_completion_needs_the_fewest_stems() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AS1a1b2\nBS2b2\nCS3a3\n\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        warehouse.best_completion(Size::Small, &[('a', 1)]),
+        Ok(Some('A'))
+    );
+    assert_eq!(
+        warehouse.best_completion(Size::Small, &[('a', 2)]),
+        Ok(Some('C'))
+    );
+    assert_eq!(
+        warehouse.best_completion(Size::Small, &[('b', 2)]),
+        Ok(Some('B'))
+    );
+    assert_eq!(
+        warehouse.best_completion(Size::Small, &[('c', 1)]),
+        Ok(None)
+    );
+    for value in ['@', '{', '€'] {
+        assert_eq!(
+            warehouse.best_completion(Size::Small, &[('a', 1), (value, 1)]),
+            Err(StemParseError::UnknownSpecies(value))
+        );
+    }
+}
+
+#[test]
+fn dominated_designs_use_the_same_species_within_the_limits() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AS1a1b2\nBS2a2b3\nCS2a2\nDS1a1b2\n\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        warehouse.dominated_designs(Size::Small),
+        [('A', 'B'), ('D', 'B')]
+    );
+}
+
+#[test]
+fn arrivals_count_the_consumed_stems_too() {
+    let mut production_line = production_line(&["AS1a1"]);
+    for species in ['a', 'a', 'b'] {
+        production_line
+            .add_stem(char_to_stem_index(species).unwrap())
+            .unwrap();
+    }
+    let arrivals = production_line.arrivals();
+    assert_eq!(arrivals[char_to_stem_index('a').unwrap().index()], 2);
+    assert_eq!(arrivals[char_to_stem_index('b').unwrap().index()], 1);
+    assert_eq!(production_line.produced_count('A'), 2);
+}
+
+#[test]
+fn on_depleted_reports_the_last_stem_of_a_species() {
+    let depleted = Arc::new(Mutex::new(Vec::new()));
+    let mut production_line = production_line(&["AS1a1b2"]);
+    let reported = Arc::clone(&depleted);
+    production_line.on_depleted(move |species| reported.lock().unwrap().push(species));
+    for species in ['a', 'b', 'b', 'b', 'a'] {
+        production_line
+            .add_stem(char_to_stem_index(species).unwrap())
+            .unwrap();
+    }
+    // The second bouquet leaves a stem of b.
+    assert_eq!(*depleted.lock().unwrap(), ['a', 'b', 'a']);
+}
+
+#[test]
+fn top_consumer_can_use_the_most_stems_of_the_species() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AS1a1b2\nBS3a3\nCS2a1b3\n\n".as_bytes())
+        .unwrap();
+    assert_eq!(warehouse.top_consumer(Size::Small, 'a'), Some('B'));
+    assert_eq!(warehouse.top_consumer(Size::Small, 'b'), Some('A'));
+    assert_eq!(warehouse.top_consumer(Size::Small, 'c'), None);
+    assert_eq!(warehouse.top_consumer(Size::Large, 'a'), None);
+}
+
+#[test]
+fn step_reports_the_change_in_stock() {
+    let mut production_line = production_line(&["AS2a2b3"]);
+    let a = char_to_stem_index('a').unwrap();
+    let step = production_line.step(a).unwrap();
+    assert_eq!(step.before, vec_to_stems(&[]).unwrap());
+    assert_eq!(step.after, vec_to_stems(&[('a', 1)]).unwrap());
+    assert_eq!(step.bouquet, None);
+    production_line.step(a).unwrap();
+    let step = production_line
+        .step(char_to_stem_index('b').unwrap())
+        .unwrap();
+    assert_eq!(step.before, vec_to_stems(&[('a', 2)]).unwrap());
+    assert_eq!(step.after, vec_to_stems(&[]).unwrap());
+    assert_eq!(step.bouquet.unwrap().to_string(), "AS2a1b");
+    assert_eq!(step.returned, vec_to_stems(&[]).unwrap());
+}
+
+#[test]
+fn shared_species_are_used_by_enough_designs() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AS1a1b2\nBS2b2\nCS1a1c2\n\n".as_bytes())
+        .unwrap();
+    assert_eq!(warehouse.shared_species(Size::Small, 2), ['a', 'b']);
+    assert_eq!(warehouse.shared_species(Size::Small, 1), ['a', 'b', 'c']);
+    assert!(warehouse.shared_species(Size::Small, 3).is_empty());
+    assert!(warehouse.shared_species(Size::Large, 0).is_empty());
+}
+
+#[test]
+fn conflicting_designs_need_more_of_a_shared_species() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AS2a2b3\nBS1a1c2\n\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        warehouse.conflict(Size::Small, 'A', 'B', &[('a', 1), ('b', 5), ('c', 5)]),
+        Ok(true)
+    );
+    assert_eq!(
+        warehouse.conflict(Size::Small, 'A', 'B', &[('a', 2)]),
+        Ok(false)
+    );
+    assert_eq!(warehouse.conflict(Size::Small, 'A', 'C', &[]), Ok(false));
+    for value in ['@', '{', '€'] {
+        assert_eq!(
+            warehouse.conflict(Size::Small, 'A', 'B', &[(value, 1)]),
+            Err(StemParseError::UnknownSpecies(value))
+        );
+    }
+}
+
+#[test]
+fn nearest_design_counts_the_stems_outside_its_range() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process("AS2a2b4\nBS3c3\n\n".as_bytes()).unwrap();
+    let bouquet = Bouquet {
+        name: 'X',
+        size: Size::Small,
+        stems: vec_to_stems(&[('a', 3), ('b', 1)]).unwrap(),
+    };
+    assert_eq!(
+        warehouse.nearest_design(Size::Small, &bouquet),
+        Some(('A', 1))
+    );
+    assert_eq!(warehouse.nearest_design(Size::Large, &bouquet), None);
+}
+
+#[test]
+fn total_demand_sums_the_maximums() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AS1a1b2\nBS3a2b4\n\n".as_bytes())
+        .unwrap();
+    assert_eq!(warehouse.total_demand(Size::Small), [('a', 4), ('b', 3)]);
+    assert!(warehouse.total_demand(Size::Large).is_empty());
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "rayon")]
+use ranger::{DesignError, DesignParseError, Size, Warehouse};
+
+fn catalog(warehouse: &Warehouse<Vec<u8>>) -> Vec<String> {
+    let mut designs: Vec<String> = Size::ALL
+        .into_iter()
+        .flat_map(|size| warehouse.designs(size).map(ToString::to_string))
+        .collect();
+    designs.sort();
+    designs
+}
+
+#[test]
+fn parallel_registration_matches_serial() {
+    let lines: Vec<String> = (0..2000)
+        .map(|index| {
+            let name = char::from(b'A' + (index % 26) as u8);
+            let size = ["S", "L", "XL"][index % 3];
+            let species = char::from(b'a' + (index % 7) as u8);
+            format!("{name}{size}{}{species}1z{}", 1 + index % 5, 2 + index % 5)
+        })
+        .collect();
+    let mut serial = Warehouse::with_writer(Vec::new());
+    let serial_errors = serial.add_designs(lines.iter().map(String::as_str), true);
+    let mut parallel = Warehouse::with_writer(Vec::new());
+    let parallel_errors = parallel.add_designs_par(lines.clone(), true);
+    assert_eq!(parallel_errors, serial_errors);
+    assert_eq!(catalog(&parallel), catalog(&serial));
+    assert!(!catalog(&parallel).is_empty());
+}
+
+#[test]
+fn parallel_registration_stops_at_the_first_error() {
+    let lines = ["AS1a1b2", "xS", "BS2c2", "yS"].map(String::from);
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    assert_eq!(
+        warehouse.add_designs_par(lines.to_vec(), false),
+        Err(vec![(
+            1,
+            DesignError::Parse(DesignParseError::InvalidName("x".to_owned()))
+        )])
+    );
+    assert_eq!(catalog(&warehouse), ["AS1a1b2"]);
+}
@@ -0,0 +1,33 @@
+#![cfg(feature = "recommend")]
+use ranger::{Size, StemParseError, Warehouse};
+
+#[test]
+fn recommends_the_designs_covering_the_supply() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AS1a1\nBS1a1b2\nCS1c1\nDS1d1\n\n".as_bytes())
+        .unwrap();
+    let subset = warehouse.recommend_subset(Size::Small, &[('a', 0.5), ('b', 0.5), ('c', 0.0)]);
+    assert_eq!(subset.unwrap(), ['B']);
+    assert_eq!(
+        warehouse
+            .recommend_subset(Size::Small, &[('a', 1.0)])
+            .unwrap(),
+        ['A']
+    );
+    assert!(warehouse
+        .recommend_subset(Size::Small, &[])
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn unknown_species_in_the_distribution_are_rejected() {
+    let warehouse = Warehouse::with_writer(Vec::new());
+    for value in ['@', '{', '€'] {
+        assert_eq!(
+            warehouse.recommend_subset(Size::Small, &[('a', 0.5), (value, 0.5)]),
+            Err(StemParseError::UnknownSpecies(value))
+        );
+    }
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "serde")]
+
+use ranger::{
+    char_to_stem_index, Design, ProductionLine, Size, SizeMap, Warehouse, WarehouseState,
+};
+
+#[test]
+fn design_round_trips() {
+    let design: Design = "BL5a1b5$12".parse().unwrap();
+    let json = serde_json::to_string(&design).unwrap();
+    assert_eq!(serde_json::from_str::<Design>(&json).unwrap(), design);
+    let sizes = SizeMap::from_fn(|size| size);
+    let json = serde_json::to_string(&sizes).unwrap();
+    assert_eq!(serde_json::from_str::<SizeMap<Size>>(&json).unwrap(), sizes);
+}
+
+#[test]
+fn production_line_resumes_after_a_round_trip() {
+    let mut production_line = ProductionLine::default();
+    production_line
+        .add_design("AS2a1b3".parse().unwrap())
+        .unwrap();
+    production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap();
+    production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap();
+    let json = serde_json::to_string(&production_line).unwrap();
+    let mut restored: ProductionLine = serde_json::from_str(&json).unwrap();
+    let bouquet = restored
+        .add_stem(char_to_stem_index('b').unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(bouquet.to_string(), "AS2a1b");
+}
+
+#[test]
+fn warehouse_state_round_trips() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process_line("AS2a1b3").unwrap();
+    warehouse.process_line("").unwrap();
+    warehouse.process_line("aS").unwrap();
+    let state = warehouse.snapshot();
+    let json = serde_json::to_string(&state).unwrap();
+    assert_eq!(
+        serde_json::from_str::<WarehouseState>(&json).unwrap(),
+        state
+    );
+}
@@ -0,0 +1,32 @@
+use ranger::{Size, SizeMap};
+
+#[test]
+fn size_map_iterates_every_size() {
+    let mut map: SizeMap<Vec<Size>> = SizeMap::default();
+    for (size, values) in map.iter_mut() {
+        values.push(size);
+    }
+    map[Size::Large].push(Size::Small);
+    let entries: Vec<(Size, usize)> = map
+        .iter()
+        .map(|(size, values)| (size, values.len()))
+        .collect();
+    assert_eq!(
+        entries,
+        [(Size::Small, 1), (Size::Large, 2), (Size::ExtraLarge, 1)]
+    );
+    assert_eq!(map[Size::ExtraLarge], [Size::ExtraLarge]);
+}
+
+#[test]
+fn sizes_round_trip_through_text_and_index() {
+    for (index, size) in Size::ALL.into_iter().enumerate() {
+        assert_eq!(size.to_string().parse::<Size>(), Ok(size));
+        assert_eq!(size.to_index(), index);
+        assert_eq!(Size::from_index(index), Some(size));
+    }
+    assert_eq!(Size::Small.to_index(), 0);
+    assert_eq!(Size::Large.to_index(), 1);
+    assert_eq!(Size::from_index(Size::ALL.len()), None);
+    assert!("M".parse::<Size>().is_err());
+}
@@ -0,0 +1,73 @@
+use ranger::{Duplicates, RegisterError, Size, Warehouse, Writers};
+
+const SAMPLE: &str = include_str!("../samples/10k.txt");
+
+fn warehouse() -> Warehouse<Vec<u8>> {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    // The sample has designs that list a species twice.
+    warehouse.on_invalid_design(|_, _| {});
+    warehouse
+}
+
+fn output(warehouse: Warehouse<Vec<u8>>) -> String {
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn restored_warehouse_continues_the_stream() {
+    let lines: Vec<&str> = SAMPLE.lines().take(2000).collect();
+    let mut uninterrupted = warehouse();
+    for line in &lines {
+        uninterrupted.process_line(line).unwrap();
+    }
+
+    let (first, second) = lines.split_at(lines.len() / 2);
+    let mut before = warehouse();
+    for line in first {
+        before.process_line(line).unwrap();
+    }
+    let state = before.snapshot();
+    let mut after = warehouse();
+    after.restore(state).unwrap();
+    for line in second {
+        after.process_line(line).unwrap();
+    }
+
+    let expected = output(uninterrupted);
+    assert!(!expected.is_empty());
+    assert_eq!(output(before) + &output(after), expected);
+}
+
+#[test]
+fn restored_stems_arrive_at_the_restore() {
+    let mut before = Warehouse::with_writer(Vec::new());
+    before.add_design("AS2a2").unwrap();
+    before.set_max_age(Size::Small, 'A', Some(1));
+    before.add_stem("aS").unwrap();
+    let mut after = Warehouse::with_writer(Vec::new());
+    after.add_design("BS1b1").unwrap();
+    for _ in 0..5 {
+        after.add_stem("bS").unwrap();
+    }
+    after.restore(before.snapshot()).unwrap();
+    after.add_stem("aS").unwrap();
+    assert_eq!(output(after), "BS1b\n".repeat(5) + "AS2a\n");
+}
+
+#[test]
+fn refused_snapshots_leave_the_warehouse_unchanged() {
+    let mut before = Warehouse::with_writer(Vec::new());
+    before.add_designs(["AS1a1", "AS1a1"], false).unwrap();
+    let mut after = Warehouse::with_writer(Vec::new());
+    after.set_duplicates(Duplicates::Reject);
+    after.add_design("BS1b1").unwrap();
+    let state = after.snapshot();
+    assert_eq!(
+        after.restore(before.snapshot()),
+        Err(RegisterError::Duplicate('A'))
+    );
+    assert_eq!(after.snapshot(), state);
+}
@@ -0,0 +1,150 @@
+use ranger::{
+    char_to_stem_index, is_species, stem_index_to_char, stem_line, stem_total, vec_to_stems, Size,
+    Species, StemCount, StemParseError, StemTotal, Stems, Warehouse, Writers, LANES, SPECIES,
+};
+
+#[test]
+fn stem_indices_round_trip() {
+    assert_eq!(Species::all().count(), SPECIES);
+    for (stem_index, species) in Species::all().enumerate() {
+        assert_eq!(species.index(), stem_index);
+        let value = stem_index_to_char(species);
+        assert!(is_species(value));
+        assert_eq!(char_to_stem_index(value).unwrap(), species);
+    }
+    assert_eq!(char_to_stem_index('z').unwrap().index(), 25);
+    assert_eq!(Species::all().nth(25).map(char::from), Some('z'));
+}
+
+#[test]
+fn species_from_char() {
+    assert_eq!(Species::from_char('a').map(Species::index), Some(0));
+    assert_eq!(Species::from_char('!'), None);
+    assert!(Species::try_from('?').is_err());
+    assert_eq!(Species::try_from('c').unwrap().to_string(), "c");
+}
+
+#[test]
+fn values_around_the_alphabet_are_not_species() {
+    for value in ['@', '{', '`', '€', '\u{1F337}'] {
+        assert!(!is_species(value), "{value:?}");
+        assert_eq!(Species::from_char(value), None);
+    }
+}
+
+#[test]
+fn stem_index_of_a_non_species_is_none() {
+    for value in ['@', '{', '€'] {
+        assert_eq!(char_to_stem_index(value), None, "{value:?}");
+        assert_eq!(
+            vec_to_stems(&[('a', 1), (value, 1)]),
+            Err(StemParseError::UnknownSpecies(value))
+        );
+    }
+}
+
+#[cfg(all(feature = "uppercase-species", not(feature = "wide-alphabet")))]
+#[test]
+fn uppercase_species_round_trip() {
+    assert_eq!(SPECIES, 52);
+    for (value, stem_index) in [('a', 0), ('z', 25), ('A', 26), ('Z', 51)] {
+        let species = char_to_stem_index(value).unwrap();
+        assert_eq!(species.index(), stem_index);
+        assert_eq!(stem_index_to_char(species), value);
+    }
+}
+
+#[cfg(feature = "uppercase-species")]
+#[test]
+fn uppercase_species_in_designs_and_stems() {
+    let mut warehouse = ranger::Warehouse::with_writer(Vec::new());
+    warehouse.process("AL1A1a2\n\nAL\naL\n".as_bytes()).unwrap();
+    let ranger::Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), "AL1a1A\n");
+}
+
+#[cfg(all(feature = "wide-alphabet", feature = "uppercase-species"))]
+#[test]
+fn wide_alphabet_and_uppercase_species_round_trip() {
+    assert_eq!((SPECIES, LANES), (84, 128));
+    for (value, stem_index) in [('z', 25), ('ß', 26), ('ÿ', 57), ('A', 58), ('Z', 83)] {
+        let species = char_to_stem_index(value).unwrap();
+        assert_eq!(species.index(), stem_index);
+        assert_eq!(stem_index_to_char(species), value);
+    }
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process("AL1Z1ÿ2\n\nZL\nÿL\n".as_bytes()).unwrap();
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), "AL1ÿ1Z\n");
+}
+
+#[cfg(all(
+    feature = "lanes-64",
+    not(all(feature = "wide-alphabet", feature = "uppercase-species"))
+))]
+#[test]
+fn wide_lanes_hold_the_same_stems() {
+    assert_eq!(LANES, 64);
+    let stems = vec_to_stems(&[('a', 2), ('z', 3)]).unwrap();
+    assert_eq!(stems.as_array().len(), 64);
+    assert_eq!(stem_total(stems), 5);
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AL2a3z5\n\naL\nzL\naL\nzL\nzL\n".as_bytes())
+        .unwrap();
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), "AL2a3z\n");
+}
+
+#[test]
+fn stem_line_is_the_species_and_the_size() {
+    assert_eq!(stem_line('a', Size::Small), "aS");
+    assert_eq!(stem_line('z', Size::ExtraLarge), "zXL");
+}
+
+#[test]
+fn totals_of_full_lanes_do_not_wrap() {
+    assert_eq!(
+        stem_total(Stems::splat(StemCount::MAX)),
+        LANES as StemTotal * StemTotal::from(StemCount::MAX)
+    );
+}
+
+/// 26 species of 200 stems, which fit in `u8` stem counts but add up to far more.
+#[test]
+fn bouquets_add_up_past_a_stem_count() {
+    let species: Vec<char> = ('a'..='z').collect();
+    let design: String = species
+        .iter()
+        .map(|species| format!("200{species}"))
+        .collect();
+    let mut input = format!("AL{design}5200\n\n");
+    for _ in 0..200 {
+        for species in &species {
+            input.push_str(&format!("{species}L\n"));
+        }
+    }
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(input.as_bytes()).unwrap();
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), format!("AL{design}\n"));
+}
+
+#[cfg(feature = "u8-stems")]
+#[test]
+fn u8_stem_counts_hold_up_to_255() {
+    assert_eq!(StemCount::MAX, 255);
+    assert!("AL255a5b260".parse::<ranger::Design>().is_ok());
+    assert_eq!(
+        "AL256a256".parse::<ranger::Design>(),
+        Err(ranger::DesignParseError::BadStemCount)
+    );
+}
@@ -0,0 +1,517 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ranger::{
+    compare_catalogs, DesignError, DesignParseError, Size, Stats, StemCount, StemError,
+    StemParseError, Warehouse, Writers, DRAIN,
+};
+
+const DESIGNS: &str = "AS1a1b2\nBS2b4\nCS3b3\n\n";
+
+fn run(input: &str) -> String {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(input.as_bytes()).unwrap();
+    match warehouse.into_writers() {
+        Writers::Shared(output) => String::from_utf8(output).unwrap(),
+        Writers::PerSize(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn count_prefix_matches_repeated_lines() {
+    let counted = run(&format!("{DESIGNS}12bS\n"));
+    let repeated = run(&format!("{DESIGNS}{}", "bS\n".repeat(12)));
+    assert_eq!(counted, repeated);
+    assert_eq!(counted.lines().count(), 4);
+}
+
+#[test]
+fn run_collects_the_bouquet_lines() {
+    let stems = ["bS", "aS", "bS", "?S", "bS", "bS"];
+    assert_eq!(
+        ranger::run(&["AS1a1b2", "BS2b4", "xS", "CS3b3"], &stems),
+        ["AS1a1b", "CS3b"]
+    );
+    assert!(ranger::run(&[], &stems).is_empty());
+}
+
+#[test]
+fn zero_count_is_rejected() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(DESIGNS.as_bytes()).unwrap();
+    assert_eq!(
+        warehouse.add_stem("0bS"),
+        Err(StemError::Parse(StemParseError::InvalidCount(
+            "0".to_string()
+        )))
+    );
+}
+
+#[test]
+fn full_stock_rejects_stems_instead_of_wrapping() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(DESIGNS.as_bytes()).unwrap();
+    warehouse
+        .add_stem(&format!("{}cS", StemCount::MAX))
+        .unwrap();
+    assert_eq!(warehouse.add_stem("cS"), Err(StemError::StockFull('c')));
+    assert_eq!(
+        *warehouse.remaining_stems().get(Size::Small),
+        vec![('c', StemCount::MAX)]
+    );
+}
+
+#[test]
+fn extra_large_stems_go_to_their_own_line() {
+    assert_eq!(
+        run("AXL1a1b2\nAL2a2\n\naXL\naL\nbXL\naL\n"),
+        "AXL1a1b\nAL2a\n"
+    );
+}
+
+#[test]
+fn bulk_lines_stop_at_the_first_error() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    assert_eq!(
+        warehouse.add_designs(["AS1a1b2", "xS", "BS2c2"], false),
+        Err(vec![(
+            1,
+            DesignError::Parse(DesignParseError::InvalidName("x".to_owned()))
+        )])
+    );
+    assert_eq!(
+        warehouse.add_stems(["aS", "cS", "?S", "bS"], false),
+        Err(vec![(
+            2,
+            StemError::Parse(StemParseError::UnknownSpecies('?'))
+        )])
+    );
+    assert_eq!(
+        *warehouse.remaining_stems().get(Size::Small),
+        vec![('a', 1), ('c', 1)]
+    );
+}
+
+#[test]
+fn bulk_lines_collect_every_error() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    let errors = warehouse
+        .add_designs(["xS", "AS1a1b2", "BS2c2", "yS"], true)
+        .unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|(position, _)| *position)
+            .collect::<Vec<_>>(),
+        vec![0, 3]
+    );
+    let errors = warehouse
+        .add_stems(["aS", "0cS", "cS", "bS", "?S"], true)
+        .unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|(position, _)| *position)
+            .collect::<Vec<_>>(),
+        vec![1, 4]
+    );
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), "AS1a1b\n");
+}
+
+#[test]
+fn finalize_makes_the_untriggered_bouquets() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(DESIGNS.as_bytes()).unwrap();
+    warehouse.pause_size(Size::Small);
+    for stem in ["aS", "bS", "bS", "bS", "bS"] {
+        warehouse.add_stem(stem).unwrap();
+    }
+    warehouse.finalize().unwrap();
+    warehouse.finalize().unwrap();
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), "AS1a1b\nCS3b\n");
+}
+
+#[test]
+fn stems_past_the_capacity_are_refused() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(DESIGNS.as_bytes()).unwrap();
+    warehouse.set_capacity(Size::Small, Some(2));
+    warehouse.add_stem("bS").unwrap();
+    warehouse.add_stem("bS").unwrap();
+    assert_eq!(warehouse.add_stem("bS"), Err(StemError::Overflow('b')));
+    assert_eq!(warehouse.add_stem("aS"), Err(StemError::Overflow('a')));
+    assert_eq!(warehouse.remaining_stems().get(Size::Small), &[('b', 2)]);
+    warehouse.add_stem("bL").unwrap();
+    assert_eq!(warehouse.utilization(Size::Small), Some(100.0));
+    assert_eq!(warehouse.utilization(Size::Large), None);
+}
+
+#[test]
+fn stock_total_counts_every_species() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(DESIGNS.as_bytes()).unwrap();
+    warehouse.set_capacity(Size::Small, Some(8));
+    for stem in ["aS", "aS", "cS", "bL"] {
+        warehouse.add_stem(stem).unwrap();
+    }
+    assert_eq!(warehouse.stock_total(Size::Small), 3);
+    assert_eq!(warehouse.stock_total(Size::Large), 1);
+    assert_eq!(warehouse.utilization(Size::Small), Some(37.5));
+    warehouse.add_stem("bS").unwrap();
+    assert_eq!(warehouse.stock_total(Size::Small), 2);
+    assert_eq!(warehouse.utilization(Size::Small), Some(25.0));
+}
+
+#[test]
+fn removed_stems_leave_the_stock() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(DESIGNS.as_bytes()).unwrap();
+    for _ in 0..3 {
+        warehouse.add_stem("aS").unwrap();
+    }
+    warehouse.remove_stem("aS").unwrap();
+    warehouse.remove_stem("aS").unwrap();
+    assert_eq!(warehouse.stem_count('a', Size::Small), 1);
+    warehouse.remove_stem("5aS").unwrap();
+    assert_eq!(warehouse.stem_count('a', Size::Small), 0);
+    warehouse.remove_stem("aS").unwrap();
+    assert_eq!(warehouse.stem_count('a', Size::Small), 0);
+    assert_eq!(
+        warehouse.remove_stem("aM"),
+        Err(StemError::Parse(StemParseError::InvalidSize(
+            "Invalid size: M".to_owned()
+        )))
+    );
+}
+
+#[test]
+fn stems_outside_the_alphabet_are_rejected() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(DESIGNS.as_bytes()).unwrap();
+    for value in ['@', '{', '€'] {
+        assert_eq!(
+            warehouse.add_stem(&format!("{value}S")),
+            Err(StemError::Parse(StemParseError::UnknownSpecies(value)))
+        );
+        assert_eq!(warehouse.stem_count(value, Size::Small), 0);
+        assert_eq!(warehouse.next_design_for(Size::Small, value), None);
+    }
+}
+
+#[test]
+fn stems_no_design_uses_are_reported() {
+    let unused = Rc::new(RefCell::new(Vec::new()));
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(DESIGNS.as_bytes()).unwrap();
+    warehouse.on_unused_stem({
+        let unused = Rc::clone(&unused);
+        move |species, size| unused.borrow_mut().push((species, size))
+    });
+    for stem in ["aS", "bL", "cS", "3cS", "bS"] {
+        warehouse.add_stem(stem).unwrap();
+    }
+    assert_eq!(
+        *unused.borrow(),
+        [('b', Size::Large), ('c', Size::Small), ('c', Size::Small)]
+    );
+    assert_eq!(warehouse.stem_count('c', Size::Small), 4);
+}
+
+#[test]
+fn surrounding_whitespace_is_ignored() {
+    let crlf = run("AS1a1b2\r\n  BS2b2 \r\n \t\r\n aS\r\nbS  \r\n\r\n");
+    assert_eq!(crlf, run("AS1a1b2\nBS2b2\n\naS\nbS\n"));
+    assert_eq!(crlf, "AS1a1b\n");
+}
+
+#[test]
+fn inventory_counts_the_stems_in_stock() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(DESIGNS.as_bytes()).unwrap();
+    for stem in ["bS", "bS", "2cS", "bL"] {
+        warehouse.add_stem(stem).unwrap();
+    }
+    assert_eq!(warehouse.stem_count('b', Size::Small), 2);
+    assert_eq!(warehouse.stem_count('b', Size::Large), 1);
+    assert_eq!(warehouse.stem_count('a', Size::Small), 0);
+    assert_eq!(warehouse.inventory(Size::Small), [('b', 2), ('c', 2)]);
+    assert_eq!(warehouse.inventory(Size::ExtraLarge), []);
+}
+
+#[test]
+fn designs_without_supply_are_starved() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AS1a1b2\nBS2b2\nCL1a1c2\n\nbS\nbS\naL\n".as_bytes())
+        .unwrap();
+    let starved = warehouse.starved_designs();
+    assert_eq!(starved[Size::Small], [('A', vec!['a'])]);
+    assert_eq!(starved[Size::Large], [('C', vec!['c'])]);
+}
+
+#[test]
+fn separator_line_delimits_the_sections() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_separator("--");
+    warehouse
+        .process("AS1a1\n\nBS1b1\n--\naS\n\nbS\n--\naS\n".as_bytes())
+        .unwrap();
+    assert_eq!(warehouse.designs(Size::Small).count(), 2);
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), "AS1a\nBS1b\n");
+}
+
+#[test]
+fn designs_past_their_quota_leave_the_stems() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process("AS1a1\nBS2a2\n\n".as_bytes()).unwrap();
+    warehouse.set_quota(Size::Small, 'A', Some(1));
+    warehouse.add_stem("aS").unwrap();
+    warehouse.add_stem("aS").unwrap();
+    assert_eq!(warehouse.stem_count('a', Size::Small), 1);
+    warehouse.add_stem("aS").unwrap();
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), "AS1a\nBS2a\n");
+}
+
+#[test]
+fn blank_lines_among_the_stems_are_ignored() {
+    assert_eq!(
+        run("AS1a1\nBS1b1\n\naS\n\nbS\n\n\naS\n"),
+        "AS1a\nBS1b\nAS1a\n"
+    );
+}
+
+#[test]
+fn leftovers_report_puts_the_most_stems_first() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process("AL5a5b5c20\n\n".as_bytes()).unwrap();
+    for stem in ["aL", "3cL", "3bL"] {
+        warehouse.add_stem(stem).unwrap();
+    }
+    let report = warehouse.leftovers_report();
+    assert_eq!(report.get(Size::Large), &[('b', 3), ('c', 3), ('a', 1)]);
+    assert!(report.get(Size::Small).is_empty());
+}
+
+#[test]
+fn simulated_stems_past_the_capacity_are_rejected() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_strict(true);
+    warehouse.add_design("AS3a3").unwrap();
+    warehouse.set_capacity(Size::Small, Some(2));
+    let stems = [('a', Size::Small); 3];
+    let stats = compare_catalogs(&[warehouse], &stems).remove(0);
+    assert_eq!(
+        stats,
+        Stats {
+            stems: 3,
+            bouquets: 0,
+            rejected: 1,
+            leftover: 2,
+        }
+    );
+}
+
+#[test]
+fn stems_past_the_max_age_are_not_used() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process("AS2a2\nBS1b1\n\n".as_bytes()).unwrap();
+    warehouse.set_max_age(Size::Small, 'A', Some(1));
+    warehouse.add_stems(["aS", "bS", "aS"], false).unwrap();
+    assert_eq!(warehouse.inventory(Size::Small), [('a', 2)]);
+    // The stock exceeds the maximum of A, but only two stems are fresh.
+    warehouse.add_stem("aS").unwrap();
+    assert_eq!(warehouse.inventory(Size::Small), [('a', 1)]);
+}
+
+#[test]
+fn precedence_holds_a_design_back() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process("AS1a1\nBS1b1\n\n".as_bytes()).unwrap();
+    warehouse.add_precedence(Size::Small, 'A', 'B');
+    warehouse.add_stems(["bS", "aS", "bS"], false).unwrap();
+    assert_eq!(warehouse.inventory(Size::Small), [('b', 1)]);
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), "AS1a\nBS1b\n");
+}
+
+#[test]
+fn validate_stems_reports_every_invalid_line() {
+    let warehouse = Warehouse::with_writer(Vec::new());
+    let errors = warehouse
+        .validate_stems("aS\n\n?S\n0aL\naM\n3bL\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        errors,
+        [
+            (3, StemParseError::UnknownSpecies('?')),
+            (4, StemParseError::InvalidCount("0".to_owned())),
+            (5, StemParseError::InvalidSize("Invalid size: M".to_owned())),
+        ]
+    );
+}
+
+#[test]
+fn lines_without_a_size_get_the_default_size() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_default_size(Some(Size::Large));
+    warehouse
+        .process("A2a2\nBS1a1\n\na\n2a\naS\n".as_bytes())
+        .unwrap();
+    assert_eq!(warehouse.designs(Size::Large).count(), 1);
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), "AL2a\nBS1a\n");
+}
+
+#[test]
+fn resumed_sizes_make_the_bouquets_of_their_stock() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(DESIGNS.as_bytes()).unwrap();
+    warehouse.pause_size(Size::Small);
+    warehouse.add_stems(["bS", "bS", "aS"], false).unwrap();
+    assert_eq!(warehouse.inventory(Size::Small), [('a', 1), ('b', 2)]);
+    warehouse.resume_size(Size::Small);
+    assert_eq!(warehouse.inventory(Size::Small), [('b', 1)]);
+    warehouse.add_stems(["aS"], false).unwrap();
+    assert_eq!(warehouse.inventory(Size::Small), []);
+}
+
+#[cfg(not(feature = "u8-stems"))]
+#[test]
+fn excess_past_a_stem_count_is_returned_without_overflowing() {
+    // Every species gets capped at `1 + total - 26` stems.
+    for total in [3000, StemCount::MAX] {
+        let cap = u32::from(total) - 25;
+        let design: String = ('a'..='z')
+            .map(|species| format!("{total}{species}"))
+            .collect();
+        let mut warehouse = Warehouse::with_writer(Vec::new());
+        warehouse.add_design(&format!("AL{design}{total}")).unwrap();
+        warehouse.pause_size(Size::Large);
+        let stems: Vec<String> = ('a'..='z')
+            .map(|species| format!("{cap}{species}L"))
+            .collect();
+        warehouse
+            .add_stems(stems.iter().map(String::as_str), false)
+            .unwrap();
+        assert_eq!(warehouse.stock_total(Size::Large), 26 * cap);
+        warehouse.resume_size(Size::Large);
+        let bouquets = 26 * cap / u32::from(total);
+        assert_eq!(
+            warehouse.stock_total(Size::Large),
+            26 * cap - bouquets * u32::from(total)
+        );
+        let Writers::Shared(output) = warehouse.into_writers() else {
+            unreachable!()
+        };
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), bouquets as usize, "{total}");
+    }
+}
+
+#[test]
+fn batch_boundaries_report_the_stock() {
+    let boundaries = Rc::new(RefCell::new(Vec::new()));
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    let reported = Rc::clone(&boundaries);
+    warehouse.on_batch_boundary(move |stock| {
+        reported.borrow_mut().push(stock.get(Size::Small).clone());
+    });
+    warehouse
+        .process("AS1a1\n\naS\nbS\n\n\nbS\n".as_bytes())
+        .unwrap();
+    assert_eq!(*boundaries.borrow(), [vec![('b', 1)], vec![('b', 2)]]);
+}
+
+#[test]
+fn revenue_sums_the_price_of_every_bouquet() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process("AS1a1$3\nBS1b1\n\n".as_bytes()).unwrap();
+    warehouse.set_price(Size::Small, 'B', 5);
+    warehouse.add_stems(["aS", "aS", "bS"], false).unwrap();
+    assert_eq!(warehouse.total_revenue(), 11);
+}
+
+#[test]
+fn designs_outside_the_active_groups_are_not_made() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AS1a1\nBS1b1\nCS1c1\n\n".as_bytes())
+        .unwrap();
+    warehouse.set_group(Size::Small, 'A', "spring");
+    warehouse.set_group(Size::Small, 'B', "summer");
+    warehouse.set_active_groups(&["summer"]);
+    warehouse.add_stems(["aS", "bS", "cS"], false).unwrap();
+    assert_eq!(warehouse.inventory(Size::Small), [('a', 1)]);
+}
+
+#[test]
+fn cascade_makes_every_bouquet_after_a_stem() {
+    let run = |cascade: bool| {
+        let mut warehouse = Warehouse::with_writer(Vec::new());
+        warehouse.set_cascade(cascade);
+        warehouse.process("AS1a1\nBS1b1\n\n".as_bytes()).unwrap();
+        warehouse.add_precedence(Size::Small, 'A', 'B');
+        warehouse.add_stems(["bS", "aS"], false).unwrap();
+        warehouse.inventory(Size::Small)
+    };
+    assert_eq!(run(false), [('b', 1)]);
+    assert_eq!(run(true), []);
+}
+
+#[test]
+fn memory_footprint_grows_with_the_history() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process(DESIGNS.as_bytes()).unwrap();
+    warehouse.set_keep_history(true);
+    let footprint = warehouse.memory_footprint();
+    assert!(footprint >= size_of::<Warehouse<Vec<u8>>>());
+    warehouse.add_stems(["aS", "bS"], false).unwrap();
+    assert!(warehouse.memory_footprint() > footprint);
+}
+
+#[test]
+fn memory_footprint_grows_with_the_designs() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    let mut footprints = Vec::new();
+    for count in [1, 10, 100] {
+        while warehouse.designs(Size::Large).count() < count {
+            warehouse.add_design("AL1a1b2").unwrap();
+        }
+        warehouse.preprocess();
+        footprints.push(warehouse.memory_footprint());
+    }
+    assert!(footprints[0] < footprints[1], "{footprints:?}");
+    assert!(footprints[1] < footprints[2], "{footprints:?}");
+}
+
+#[test]
+fn drain_line_makes_the_bouquets_of_paused_sizes() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.process_line("AS1a1").unwrap();
+    warehouse.process_line("").unwrap();
+    warehouse.pause_size(Size::Small);
+    for line in ["aS", "aS", DRAIN, "aS"] {
+        warehouse.process_line(line).unwrap();
+    }
+    assert_eq!(warehouse.inventory(Size::Small), [('a', 1)]);
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    assert_eq!(String::from_utf8(output).unwrap(), "AS1a\nAS1a\n");
+}
@@ -0,0 +1,68 @@
+use ranger::{
+    char_to_stem_index, compare_catalogs, Design, ProductionLine, Size, Stats, Strategy, Warehouse,
+    Writers,
+};
+
+fn run(strategy: Strategy) -> String {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse.set_strategy(strategy);
+    warehouse
+        .process("AS1a1b2\nBS1a1b1c3\n\ncS\naS\nbS\n".as_bytes())
+        .unwrap();
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn simplest_first_makes_the_smallest_design() {
+    assert_eq!(run(Strategy::SimplestFirst), "AS1a1b\n");
+}
+
+#[test]
+fn minimize_excess_uses_the_most_stock() {
+    assert_eq!(run(Strategy::MinimizeExcess), "BS1a1b1c\n");
+}
+
+#[test]
+fn consume_arriving_uses_the_most_of_the_species() {
+    let mut production_line = ProductionLine::default();
+    for design in ["AS1a1b2", "BS3b3"] {
+        production_line
+            .add_design(design.parse::<Design>().unwrap())
+            .unwrap();
+    }
+    production_line.set_strategy(Strategy::ConsumeArriving);
+    production_line.preprocess();
+    production_line.pause();
+    for species in ['a', 'b', 'b', 'b'] {
+        production_line
+            .add_stem(char_to_stem_index(species).unwrap())
+            .unwrap();
+    }
+    let bouquet = production_line
+        .try_make_design(char_to_stem_index('b').unwrap())
+        .unwrap();
+    assert_eq!(bouquet.to_string(), "BS3b");
+}
+
+#[test]
+fn simulate_priority_prefers_the_design() {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    warehouse
+        .process("AS1a1b2\nBS1a2b3\n\n".as_bytes())
+        .unwrap();
+    let stems = [('b', Size::Small), ('b', Size::Small), ('a', Size::Small)];
+    assert_eq!(
+        warehouse.simulate_priority('B', &stems),
+        Stats {
+            stems: 3,
+            bouquets: 1,
+            rejected: 0,
+            leftover: 0,
+        }
+    );
+    // Without the priority the simplest design is made, leaving a stem.
+    assert_eq!(compare_catalogs(&[warehouse], &stems)[0].leftover, 1);
+}
@@ -0,0 +1,55 @@
+use ranger::{char_to_stem_index, Design, ProductionLine, TieBreak, Warehouse, Writers};
+
+fn first_bouquet(tie_break: Option<TieBreak>) -> String {
+    let mut warehouse = Warehouse::with_writer(Vec::new());
+    if let Some(tie_break) = tie_break {
+        warehouse.set_tie_break(tie_break);
+    }
+    warehouse
+        .process("BS1a1\nAS1a1\n\naS\n".as_bytes())
+        .unwrap();
+    let Writers::Shared(output) = warehouse.into_writers() else {
+        unreachable!()
+    };
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn equal_totals_are_picked_by_name() {
+    assert_eq!(first_bouquet(None), "AS1a\n");
+}
+
+#[test]
+fn registration_tie_break_picks_the_first_registered() {
+    assert_eq!(first_bouquet(Some(TieBreak::Registration)), "BS1a\n");
+}
+
+#[test]
+fn registration_tie_break_undoes_an_earlier_sort_by_name() {
+    let mut production_line = ProductionLine::default();
+    for design in ["BS1a1", "AS1a1"] {
+        production_line
+            .add_design(design.parse::<Design>().unwrap())
+            .unwrap();
+    }
+    production_line.preprocess();
+    production_line.set_tie_break(TieBreak::Registration);
+    let bouquet = production_line
+        .add_stem(char_to_stem_index('a').unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(bouquet.name, 'B');
+}
+
+#[test]
+fn hashed_tie_break_is_stable_per_seed_and_varies_across_seeds() {
+    let picks: Vec<String> = (0..16)
+        .map(|seed| first_bouquet(Some(TieBreak::Hashed { seed })))
+        .collect();
+    for (seed, pick) in picks.iter().enumerate() {
+        let seed = seed as u64;
+        assert_eq!(&first_bouquet(Some(TieBreak::Hashed { seed })), pick);
+    }
+    assert!(picks.iter().any(|pick| pick == "AS1a\n"));
+    assert!(picks.iter().any(|pick| pick == "BS1a\n"));
+}